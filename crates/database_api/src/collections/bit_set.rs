@@ -0,0 +1,231 @@
+use alloc::{collections::BTreeSet, vec::Vec};
+
+#[cfg(feature = "std")]
+use std::collections::HashSet;
+
+use crate::traits::{KeySet, KeySetAlgebra};
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// A [KeySet] over dense integer keys, storing one bit per possible key.
+///
+/// Set operations between two bitsets combine them a word at a time,
+/// which makes intersecting the key sets of many columns cheap.
+///
+/// Iterating a [KeySet] hands out references to its keys, so alongside its bits
+/// a bitset keeps a table of every key it has room for, to reference them from.
+#[derive(Debug, Default, Clone)]
+pub struct BitSet {
+    words: Vec<u64>,
+    keys: Vec<usize>,
+}
+
+impl BitSet {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create a set with room for keys below `capacity` without reallocating.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let words = capacity.div_ceil(WORD_BITS);
+        BitSet {
+            words: Vec::with_capacity(words),
+            keys: Vec::with_capacity(words * WORD_BITS),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|word| *word == 0)
+    }
+
+    pub fn clear(&mut self) {
+        self.words.clear()
+    }
+
+    /// Make room for `words` words, extending the key table to match.
+    fn grow(&mut self, words: usize) {
+        if words > self.words.len() {
+            self.words.resize(words, 0);
+        }
+
+        let keys = self.keys.len();
+        if words * WORD_BITS > keys {
+            self.keys.extend(keys..words * WORD_BITS);
+        }
+    }
+
+    /// Drop trailing empty words, so equal sets compare equal.
+    fn trim(&mut self) {
+        while self.words.last() == Some(&0) {
+            self.words.pop();
+        }
+    }
+
+    /// Keep only the keys for which `f` returns true.
+    pub fn retain(&mut self, mut f: impl FnMut(&usize) -> bool) {
+        for (i, word) in self.words.iter_mut().enumerate() {
+            let mut bits = *word;
+            while bits != 0 {
+                let bit = bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                if !f(&(i * WORD_BITS + bit)) {
+                    *word &= !(1 << bit);
+                }
+            }
+        }
+        self.trim();
+    }
+}
+
+impl PartialEq for BitSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.words == other.words
+    }
+}
+
+impl Eq for BitSet {}
+
+impl core::iter::FromIterator<usize> for BitSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut set = BitSet::new();
+        KeySet::extend(&mut set, iter.into_iter());
+        set
+    }
+}
+
+impl<'a> KeySet<'a, usize> for BitSet {
+    type Iter = BitSetIter<'a>;
+
+    fn insert(&mut self, key: usize) -> bool {
+        let (word, bit) = (key / WORD_BITS, 1 << (key % WORD_BITS));
+        self.grow(word + 1);
+
+        let inserted = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        inserted
+    }
+
+    fn extend(&mut self, values: impl Iterator<Item = usize>) {
+        for key in values {
+            KeySet::insert(self, key);
+        }
+    }
+
+    fn contains(&self, key: &usize) -> bool {
+        self.words
+            .get(key / WORD_BITS)
+            .is_some_and(|word| word & (1 << (key % WORD_BITS)) != 0)
+    }
+
+    fn remove(&mut self, key: &usize) -> bool {
+        let (word, bit) = (key / WORD_BITS, 1 << (key % WORD_BITS));
+        match self.words.get_mut(word) {
+            Some(word) if *word & bit != 0 => {
+                *word &= !bit;
+                self.trim();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn iter(&'a self) -> Self::Iter {
+        BitSetIter {
+            words: self.words.iter().enumerate(),
+            keys: &self.keys,
+            base: 0,
+            bits: 0,
+        }
+    }
+
+    fn len(&'a self) -> usize {
+        BitSet::len(self)
+    }
+
+    fn is_empty(&'a self) -> bool {
+        BitSet::is_empty(self)
+    }
+}
+
+impl<'b> KeySetAlgebra<'b, BitSet> for BitSet {
+    fn intersect_with(&mut self, other: &'b BitSet) {
+        self.words.truncate(other.words.len());
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word &= other;
+        }
+        self.trim();
+    }
+
+    fn union_with(&mut self, other: &'b BitSet) {
+        self.grow(other.words.len());
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word |= other;
+        }
+    }
+
+    fn difference_with(&mut self, other: &'b BitSet) {
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word &= !other;
+        }
+        self.trim();
+    }
+}
+
+/// Combine a bitset with another kind of [KeySet] a key at a time.
+macro_rules! impl_key_set_algebra {
+    ($($(#[$attr:meta])* $ty:ty),* $(,)?) => {
+        $(
+            $(#[$attr])*
+            impl<'b> KeySetAlgebra<'b, $ty> for BitSet {
+                fn intersect_with(&mut self, other: &'b $ty) {
+                    self.retain(|key| other.contains(key))
+                }
+
+                fn union_with(&mut self, other: &'b $ty) {
+                    KeySet::extend(self, other.iter().copied())
+                }
+
+                fn difference_with(&mut self, other: &'b $ty) {
+                    self.retain(|key| !other.contains(key))
+                }
+            }
+        )*
+    };
+}
+
+impl_key_set_algebra!(
+    BTreeSet<usize>,
+    #[cfg(feature = "std")]
+    HashSet<usize>,
+);
+
+/// Iterator over the keys of a [BitSet], in ascending order.
+pub struct BitSetIter<'a> {
+    words: core::iter::Enumerate<core::slice::Iter<'a, u64>>,
+    keys: &'a [usize],
+    base: usize,
+    bits: u64,
+}
+
+impl<'a> Iterator for BitSetIter<'a> {
+    type Item = &'a usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.bits == 0 {
+            let (index, word) = self.words.next()?;
+            self.base = index * WORD_BITS;
+            self.bits = *word;
+        }
+
+        let bit = self.bits.trailing_zeros() as usize;
+        self.bits &= self.bits - 1;
+        Some(&self.keys[self.base + bit])
+    }
+}
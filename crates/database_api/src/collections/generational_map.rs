@@ -0,0 +1,139 @@
+use alloc::vec::Vec;
+
+use crate::{
+    table::Generational,
+    traits::{KeyError, KeyValueMap},
+};
+
+#[derive(Debug, Clone)]
+struct Slot<K, V> {
+    key: K,
+    value: V,
+}
+
+/// A [KeyValueMap] over [Generational] keys such as [GenerationalKey](crate::GenerationalKey),
+/// stored in a vector indexed by key index.
+///
+/// Each slot remembers the generation of the key it was inserted under,
+/// so looking up a stale key reports [KeyError::Stale]
+/// instead of returning the data of the entity that replaced it.
+#[derive(Debug, Clone)]
+pub struct GenerationalMap<K, V> {
+    slots: Vec<Option<Slot<K, V>>>,
+}
+
+impl<K, V> Default for GenerationalMap<K, V> {
+    fn default() -> Self {
+        GenerationalMap {
+            slots: Default::default(),
+        }
+    }
+}
+
+impl<K, V> GenerationalMap<K, V> {
+    pub fn len(&self) -> usize {
+        self.slots.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.iter().all(Option::is_none)
+    }
+}
+
+impl<K, V> GenerationalMap<K, V>
+where
+    K: Generational,
+{
+    fn slot(&self, key: &K) -> Result<&Slot<K, V>, KeyError> {
+        match self.slots.get(key.index()) {
+            Some(Some(slot)) if slot.key == *key => Ok(slot),
+            Some(Some(slot)) if is_older(key, &slot.key) => Err(KeyError::Stale),
+            _ => Err(KeyError::Missing),
+        }
+    }
+
+    fn slot_mut(&mut self, key: &K) -> Option<&mut Slot<K, V>> {
+        match self.slots.get_mut(key.index()) {
+            Some(Some(slot)) if slot.key == *key => Some(slot),
+            _ => None,
+        }
+    }
+}
+
+/// Generations wrap around, so compare them by their distance
+/// rather than their value, as with TCP sequence numbers.
+fn is_older<K: Generational>(key: &K, than: &K) -> bool {
+    (key.generation().wrapping_sub(than.generation()) as i32) < 0
+}
+
+impl<'a, K, V> KeyValueMap<'a, K, V> for GenerationalMap<K, V>
+where
+    K: Generational + 'a,
+    V: 'a,
+{
+    type Keys = GenerationalMapKeys<'a, K, V>;
+
+    /// Inserting under a newer generation replaces the data of the stale key it recycles.
+    ///
+    /// Inserting under a stale key leaves the newer entity in place and drops `value`;
+    /// use [KeyValueMap::try_insert] to find out when that happens.
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.try_insert(key, value).unwrap_or(None)
+    }
+
+    fn try_insert(&mut self, key: K, value: V) -> Result<Option<V>, KeyError> {
+        if key.index() >= self.slots.len() {
+            self.slots.resize_with(key.index() + 1, || None);
+        }
+
+        let slot = &mut self.slots[key.index()];
+        match slot {
+            Some(existing) if existing.key == key => {
+                Ok(Some(core::mem::replace(&mut existing.value, value)))
+            }
+            Some(existing) if is_older(&key, &existing.key) => Err(KeyError::Stale),
+            _ => {
+                *slot = Some(Slot { key, value });
+                Ok(None)
+            }
+        }
+    }
+
+    fn extend(&mut self, values: impl Iterator<Item = (K, V)>) {
+        for (key, value) in values {
+            KeyValueMap::insert(self, key, value);
+        }
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        self.slot(key).ok().map(|slot| &slot.value)
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.slot_mut(key).map(|slot| &mut slot.value)
+    }
+
+    fn lookup(&self, key: &K) -> Result<&V, KeyError> {
+        self.slot(key).map(|slot| &slot.value)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        self.slot_mut(key)?;
+        self.slots[key.index()].take().map(|slot| slot.value)
+    }
+
+    fn keys(&'a self) -> Self::Keys {
+        GenerationalMapKeys(self.slots.iter())
+    }
+}
+
+/// Iterator over the keys of a [GenerationalMap], in index order.
+pub struct GenerationalMapKeys<'a, K, V>(core::slice::Iter<'a, Option<Slot<K, V>>>);
+
+impl<'a, K, V> Iterator for GenerationalMapKeys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.find_map(|slot| slot.as_ref().map(|slot| &slot.key))
+    }
+}
@@ -0,0 +1,13 @@
+mod bit_set;
+mod generational_map;
+mod sparse_set;
+
+#[cfg(feature = "std")]
+mod sharded_map;
+
+pub use bit_set::*;
+pub use generational_map::*;
+pub use sparse_set::*;
+
+#[cfg(feature = "std")]
+pub use sharded_map::*;
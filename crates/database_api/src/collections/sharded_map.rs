@@ -0,0 +1,365 @@
+use std::{
+    cell::Cell,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use crate::traits::{KeyValueMap, MapLock};
+
+const DEFAULT_SHARDS: usize = 16;
+
+/// A [HashMap] split into a number of shards, selected by key hash, that each have their own lock.
+///
+/// Serves as both the outer lock and the cell map of a [Column](crate::Column),
+/// i.e. `readings: ShardedMap<usize, RwLock<i64>>`.
+/// Its guards lock shards rather than the whole map: inserts and removes only lock the shard
+/// their key hashes to, for as long as they take, so writers on different threads proceed
+/// in parallel unless their keys share a shard.
+/// Lookups and key iteration lock every shard for reading, in order, and hold them until the guard
+/// is dropped or next written through.
+///
+/// Since a write guard holds no shard once an insert or remove returns, its writes are visible
+/// to other guards as soon as they're made. A [Transaction](crate::Transaction) over a `ShardedMap`
+/// column is therefore not atomic: concurrent readers can see some of its operations before
+/// the commit finishes, or before a failed commit rolls them back.
+/// Holding every shard written until the guard drops would fix that, but writers reaching
+/// shards in different orders could then deadlock, and parallel writers would serialize
+/// on the first shard they share.
+pub struct ShardedMap<K, V, S = RandomState> {
+    hasher: S,
+    shards: Box<[RwLock<HashMap<K, V, S>>]>,
+}
+
+impl<K, V, S> ShardedMap<K, V, S>
+where
+    S: BuildHasher + Default,
+{
+    pub fn with_shards(shards: usize) -> Self {
+        Self::with_shards_and_hasher(shards, S::default())
+    }
+
+    pub fn with_shards_and_hasher(shards: usize, hasher: S) -> Self {
+        assert!(shards > 0, "A ShardedMap needs at least one shard");
+        ShardedMap {
+            hasher,
+            shards: (0..shards)
+                .map(|_| RwLock::new(HashMap::with_hasher(S::default())))
+                .collect(),
+        }
+    }
+}
+
+impl<K, V, S> ShardedMap<K, V, S> {
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// The number of entries, counted one shard at a time.
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| read_shard(shard).len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| read_shard(shard).is_empty())
+    }
+}
+
+impl<K, V, S> ShardedMap<K, V, S>
+where
+    K: Hash,
+    S: BuildHasher,
+{
+    fn shard_index(&self, key: &K) -> usize {
+        (self.hasher.hash_one(key) as usize) % self.shards.len()
+    }
+}
+
+impl<K, V, S> Default for ShardedMap<K, V, S>
+where
+    S: BuildHasher + Default,
+{
+    fn default() -> Self {
+        Self::with_shards(DEFAULT_SHARDS)
+    }
+}
+
+impl<K, V, S> std::fmt::Debug for ShardedMap<K, V, S>
+where
+    K: std::fmt::Debug,
+    V: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut map = f.debug_map();
+        for shard in self.shards.iter() {
+            map.entries(read_shard(shard).iter());
+        }
+        map.finish()
+    }
+}
+
+impl<'a, K, V, S> MapLock<'a, ShardedCells<'a, K, V, S>> for ShardedMap<K, V, S>
+where
+    S: BuildHasher + Default + 'a,
+    K: 'a,
+    V: 'a,
+{
+    type ReadGuard = ShardedReadGuard<'a, K, V, S>;
+    type WriteGuard = ShardedWriteGuard<'a, K, V, S>;
+
+    fn read(&'a self) -> Self::ReadGuard {
+        ShardedReadGuard(ShardedCells::new(self))
+    }
+
+    fn write(&'a self) -> Self::WriteGuard {
+        ShardedWriteGuard(ShardedCells::new(self))
+    }
+}
+
+fn read_shard<T>(shard: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    shard.read().expect("poisoned")
+}
+
+fn write_shard<T>(shard: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    shard.write().expect("poisoned")
+}
+
+enum ShardGuards<'a, K, V, S> {
+    Read(Vec<RwLockReadGuard<'a, HashMap<K, V, S>>>),
+    Write(Vec<RwLockWriteGuard<'a, HashMap<K, V, S>>>),
+}
+
+/// The cell map of a [ShardedMap] column, as seen through one of its guards.
+///
+/// Starts out holding no shard locks.
+/// [KeyValueMap::insert] and [KeyValueMap::remove] lock the key's shard for the duration of the call,
+/// [KeyValueMap::get] and [KeyValueMap::keys] read-lock every shard until the next write,
+/// and [KeyValueMap::get_mut] write-locks every shard.
+/// Shards are always locked in index order, so guards on different threads can't deadlock each other.
+/// Writes aren't isolated from other guards; see [ShardedMap].
+pub struct ShardedCells<'a, K, V, S = RandomState> {
+    map: &'a ShardedMap<K, V, S>,
+    // Boxed ShardGuards, filled in by the first lookup.
+    // Type-erased, since storing them in a cell directly would make the cells invariant over 'a.
+    guards: Cell<Option<NonNull<()>>>,
+    _phantom: PhantomData<ShardGuards<'a, K, V, S>>,
+}
+
+impl<'a, K, V, S> ShardedCells<'a, K, V, S> {
+    fn new(map: &'a ShardedMap<K, V, S>) -> Self {
+        ShardedCells {
+            map,
+            guards: Cell::new(None),
+            _phantom: PhantomData,
+        }
+    }
+
+    fn guards(&self) -> Option<&ShardGuards<'a, K, V, S>> {
+        // SAFETY: The pointer comes from a boxed ShardGuards, which is only freed through `&mut self`.
+        self.guards
+            .get()
+            .map(|guards| unsafe { &*guards.cast::<ShardGuards<'a, K, V, S>>().as_ptr() })
+    }
+
+    fn guards_mut(&mut self) -> Option<&mut ShardGuards<'a, K, V, S>> {
+        // SAFETY: As in guards, and `&mut self` makes the access unique.
+        self.guards
+            .get()
+            .map(|guards| unsafe { &mut *guards.cast::<ShardGuards<'a, K, V, S>>().as_ptr() })
+    }
+
+    fn set_guards(&self, guards: ShardGuards<'a, K, V, S>) -> &ShardGuards<'a, K, V, S> {
+        debug_assert!(self.guards.get().is_none());
+        let guards = NonNull::from(Box::leak(Box::new(guards)));
+        self.guards.set(Some(guards.cast()));
+        // SAFETY: Freshly leaked above.
+        unsafe { &*guards.as_ptr() }
+    }
+
+    /// Unlock any shards held by the cells.
+    fn release(&mut self) {
+        if let Some(guards) = self.guards.take() {
+            // SAFETY: The pointer comes from a leaked Box in set_guards, and was just taken out of the cell.
+            drop(unsafe { Box::from_raw(guards.cast::<ShardGuards<'a, K, V, S>>().as_ptr()) });
+        }
+    }
+
+    fn read_shards(&self) -> &ShardGuards<'a, K, V, S> {
+        match self.guards() {
+            Some(guards) => guards,
+            None => self.set_guards(ShardGuards::Read(
+                self.map.shards.iter().map(read_shard).collect(),
+            )),
+        }
+    }
+
+    fn write_shards(&mut self) -> &mut Vec<RwLockWriteGuard<'a, HashMap<K, V, S>>> {
+        if !matches!(self.guards(), Some(ShardGuards::Write(_))) {
+            // Release any read locks first, or this thread would wait on itself
+            self.release();
+            self.set_guards(ShardGuards::Write(
+                self.map.shards.iter().map(write_shard).collect(),
+            ));
+        }
+
+        match self.guards_mut() {
+            Some(ShardGuards::Write(guards)) => guards,
+            _ => unreachable!(),
+        }
+    }
+
+    fn shards(&self) -> impl Iterator<Item = &HashMap<K, V, S>> {
+        let (read, write) = match self.read_shards() {
+            ShardGuards::Read(guards) => (&guards[..], &[][..]),
+            ShardGuards::Write(guards) => (&[][..], &guards[..]),
+        };
+
+        read.iter()
+            .map(Deref::deref)
+            .chain(write.iter().map(Deref::deref))
+    }
+
+    /// Run `f` on a shard, write-locking only that shard unless every shard is already locked.
+    fn with_shard<R>(&mut self, index: usize, f: impl FnOnce(&mut HashMap<K, V, S>) -> R) -> R {
+        if let Some(ShardGuards::Write(guards)) = self.guards_mut() {
+            return f(&mut guards[index]);
+        }
+
+        self.release();
+        f(&mut write_shard(&self.map.shards[index]))
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards().map(HashMap::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards().all(HashMap::is_empty)
+    }
+}
+
+impl<'a, K, V, S> Drop for ShardedCells<'a, K, V, S> {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+impl<'a, K, V, S> ShardedCells<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    fn shard(&self, key: &K) -> &HashMap<K, V, S> {
+        let index = self.map.shard_index(key);
+        match self.read_shards() {
+            ShardGuards::Read(guards) => &guards[index],
+            ShardGuards::Write(guards) => &guards[index],
+        }
+    }
+}
+
+impl<'a, K, V, S> std::fmt::Debug for ShardedCells<'a, K, V, S>
+where
+    K: std::fmt::Debug,
+    V: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.shards().flatten()).finish()
+    }
+}
+
+impl<'a, 'b, K, V, S> KeyValueMap<'b, K, V> for ShardedCells<'a, K, V, S>
+where
+    'a: 'b,
+    K: Hash + Eq + 'b,
+    V: 'b,
+    S: BuildHasher,
+{
+    type Keys = ShardedKeys<'b, K, V, S>;
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let index = self.map.shard_index(&key);
+        self.with_shard(index, |shard| shard.insert(key, value))
+    }
+
+    fn extend(&mut self, values: impl Iterator<Item = (K, V)>) {
+        for (key, value) in values {
+            KeyValueMap::insert(self, key, value);
+        }
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        self.shard(key).get(key)
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let index = self.map.shard_index(key);
+        self.write_shards()[index].get_mut(key)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        let index = self.map.shard_index(key);
+        self.with_shard(index, |shard| shard.remove(key))
+    }
+
+    fn keys(&'b self) -> Self::Keys {
+        ShardedKeys {
+            shards: Box::new(self.shards()),
+            keys: None,
+        }
+    }
+}
+
+/// Iterator over the keys of a [ShardedCells], shard by shard.
+pub struct ShardedKeys<'a, K, V, S> {
+    shards: Box<dyn Iterator<Item = &'a HashMap<K, V, S>> + 'a>,
+    keys: Option<std::collections::hash_map::Keys<'a, K, V>>,
+}
+
+impl<'a, K, V, S> Iterator for ShardedKeys<'a, K, V, S> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(key) = self.keys.as_mut().and_then(Iterator::next) {
+                return Some(key);
+            }
+
+            self.keys = Some(self.shards.next()?.keys());
+        }
+    }
+}
+
+/// Read guard of a [ShardedMap].
+pub struct ShardedReadGuard<'a, K, V, S = RandomState>(ShardedCells<'a, K, V, S>);
+
+impl<'a, K, V, S> Deref for ShardedReadGuard<'a, K, V, S> {
+    type Target = ShardedCells<'a, K, V, S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Write guard of a [ShardedMap].
+pub struct ShardedWriteGuard<'a, K, V, S = RandomState>(ShardedCells<'a, K, V, S>);
+
+impl<'a, K, V, S> Deref for ShardedWriteGuard<'a, K, V, S> {
+    type Target = ShardedCells<'a, K, V, S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'a, K, V, S> DerefMut for ShardedWriteGuard<'a, K, V, S> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
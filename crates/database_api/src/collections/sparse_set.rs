@@ -0,0 +1,133 @@
+use alloc::vec::Vec;
+
+use crate::traits::KeyValueMap;
+
+/// A key that maps directly onto an index into a [SparseSet].
+pub trait SparseIndex: Copy {
+    fn sparse_index(&self) -> usize;
+}
+
+macro_rules! impl_sparse_index {
+    ($($ty:ty),*) => {
+        $(
+            impl SparseIndex for $ty {
+                fn sparse_index(&self) -> usize {
+                    *self as usize
+                }
+            }
+        )*
+    };
+}
+
+impl_sparse_index!(u8, u16, u32, usize);
+
+/// A [KeyValueMap] over dense integer keys, such as those handed out by [NextKey](crate::NextKey).
+///
+/// Keeps a sparse array from key to position, alongside packed arrays of keys and values.
+/// Insert, remove and lookup are O(1), and iteration only visits occupied entries.
+/// Removal swaps the last entry into the freed position, so iteration order is not insertion order.
+#[derive(Debug, Clone)]
+pub struct SparseSet<K, V> {
+    sparse: Vec<Option<usize>>,
+    keys: Vec<K>,
+    values: Vec<V>,
+}
+
+impl<K, V> Default for SparseSet<K, V> {
+    fn default() -> Self {
+        SparseSet {
+            sparse: Default::default(),
+            keys: Default::default(),
+            values: Default::default(),
+        }
+    }
+}
+
+impl<K, V> SparseSet<K, V> {
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The packed values, in the same order as [KeyValueMap::keys].
+    pub fn values(&self) -> &[V] {
+        &self.values
+    }
+
+    pub fn values_mut(&mut self) -> &mut [V] {
+        &mut self.values
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.keys.iter().zip(self.values.iter())
+    }
+}
+
+impl<K, V> SparseSet<K, V>
+where
+    K: SparseIndex,
+{
+    fn dense_index(&self, key: &K) -> Option<usize> {
+        self.sparse.get(key.sparse_index()).copied().flatten()
+    }
+}
+
+impl<'a, K, V> KeyValueMap<'a, K, V> for SparseSet<K, V>
+where
+    K: SparseIndex + 'a,
+    V: 'a,
+{
+    type Keys = core::slice::Iter<'a, K>;
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(dense) = self.dense_index(&key) {
+            return Some(core::mem::replace(&mut self.values[dense], value));
+        }
+
+        let index = key.sparse_index();
+        if index >= self.sparse.len() {
+            self.sparse.resize(index + 1, None);
+        }
+
+        self.sparse[index] = Some(self.values.len());
+        self.keys.push(key);
+        self.values.push(value);
+        None
+    }
+
+    fn extend(&mut self, values: impl Iterator<Item = (K, V)>) {
+        for (key, value) in values {
+            KeyValueMap::insert(self, key, value);
+        }
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        self.dense_index(key).map(|dense| &self.values[dense])
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.dense_index(key)
+            .map(move |dense| &mut self.values[dense])
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        let dense = self.sparse.get_mut(key.sparse_index())?.take()?;
+
+        self.keys.swap_remove(dense);
+        let value = self.values.swap_remove(dense);
+
+        // Point the entry that was swapped into the hole at its new position
+        if let Some(moved) = self.keys.get(dense) {
+            self.sparse[moved.sparse_index()] = Some(dense);
+        }
+
+        Some(value)
+    }
+
+    fn keys(&'a self) -> Self::Keys {
+        self.keys.iter()
+    }
+}
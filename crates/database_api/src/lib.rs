@@ -1,10 +1,30 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+// Lets the derives refer to this crate as ::database_api from within it
+extern crate self as database_api;
+
+pub mod collections;
+pub mod locks;
+pub mod query;
 pub mod table;
 pub mod traits;
 
+pub use collections::*;
+pub use locks::*;
+pub use query::*;
 pub use table::*;
 pub use traits::*;
 
 pub use database_api_macros as macros;
 
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::{boxed::Box, vec::Vec};
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test;
 #[cfg(test)]
-mod test;
\ No newline at end of file
+mod test_alloc;
\ No newline at end of file
@@ -0,0 +1,205 @@
+use core::{
+    cell::UnsafeCell,
+    hint::spin_loop,
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+    ptr,
+    sync::atomic::{fence, AtomicBool, AtomicUsize, Ordering},
+};
+
+use crate::traits::Lock;
+
+/// A seqlock cell for small [Copy] values, suitable as the inner lock of a [Column](crate::Column).
+///
+/// Readers copy the value out and retry if a write overlapped,
+/// so they never block a writer and never hold anything once their guard is created,
+/// though they spin while a value is being stored.
+/// Writers take a spin lock against each other for the whole lifetime of their guard,
+/// which makes read-modify-write through a write guard safe from lost updates,
+/// so it is not lock-free: a writer waits for as long as another holds its guard.
+pub struct Atomic<T> {
+    sequence: AtomicUsize,
+    writer: AtomicBool,
+    value: UnsafeCell<T>,
+}
+
+// SAFETY: Writers are serialized by the `writer` flag, and readers only ever copy the value out,
+// discarding any copy that overlapped a write.
+unsafe impl<T> Sync for Atomic<T> where T: Copy + Send {}
+
+impl<T> Atomic<T>
+where
+    T: Copy,
+{
+    pub fn new(value: T) -> Self {
+        Atomic {
+            sequence: AtomicUsize::new(0),
+            writer: AtomicBool::new(false),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Read the current value.
+    pub fn load(&self) -> T {
+        loop {
+            let before = self.sequence.load(Ordering::Acquire);
+            if before & 1 == 0 {
+                // SAFETY: The read may tear if it overlaps a write, so it's taken as possibly
+                // uninitialized bytes rather than a T, which could have invalid bit patterns.
+                let value =
+                    unsafe { ptr::read_volatile(self.value.get() as *const MaybeUninit<T>) };
+                fence(Ordering::Acquire);
+                if self.sequence.load(Ordering::Relaxed) == before {
+                    // SAFETY: No write overlapped the read, so it copied a whole T.
+                    return unsafe { value.assume_init() };
+                }
+            }
+            spin_loop();
+        }
+    }
+
+    /// Replace the current value.
+    pub fn store(&self, value: T) {
+        self.lock_writer();
+        self.publish(value);
+        self.unlock_writer();
+    }
+
+    fn lock_writer(&self) {
+        while self
+            .writer
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            spin_loop();
+        }
+    }
+
+    fn unlock_writer(&self) {
+        self.writer.store(false, Ordering::Release);
+    }
+
+    /// Must only be called while holding the writer lock.
+    fn publish(&self, value: T) {
+        let sequence = self.sequence.load(Ordering::Relaxed);
+        self.sequence
+            .store(sequence.wrapping_add(1), Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        // SAFETY: The writer lock is held, so this is the only write in progress.
+        unsafe { ptr::write_volatile(self.value.get(), value) };
+
+        self.sequence
+            .store(sequence.wrapping_add(2), Ordering::Release);
+    }
+}
+
+impl<T> Default for Atomic<T>
+where
+    T: Copy + Default,
+{
+    fn default() -> Self {
+        Atomic::new(T::default())
+    }
+}
+
+impl<T> From<T> for Atomic<T>
+where
+    T: Copy,
+{
+    fn from(value: T) -> Self {
+        Atomic::new(value)
+    }
+}
+
+impl<T> Clone for Atomic<T>
+where
+    T: Copy,
+{
+    fn clone(&self) -> Self {
+        Atomic::new(self.load())
+    }
+}
+
+impl<T> core::fmt::Debug for Atomic<T>
+where
+    T: Copy + core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("Atomic").field(&self.load()).finish()
+    }
+}
+
+impl<'a, T> Lock<'a, T> for Atomic<T>
+where
+    T: Copy + Default + 'a,
+{
+    type ReadGuard = AtomicReadGuard<T>;
+    type WriteGuard = AtomicWriteGuard<'a, T>;
+
+    fn read(&'a self) -> Self::ReadGuard {
+        AtomicReadGuard(self.load())
+    }
+
+    fn write(&'a self) -> Self::WriteGuard {
+        self.lock_writer();
+        AtomicWriteGuard {
+            cell: self,
+            value: self.load(),
+        }
+    }
+    fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+/// A read guard holding a copy of an [Atomic] cell's value.
+#[derive(Debug, Copy, Clone)]
+pub struct AtomicReadGuard<T>(T);
+
+impl<T> Deref for AtomicReadGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// A write guard holding a copy of an [Atomic] cell's value, which is stored back on drop.
+pub struct AtomicWriteGuard<'a, T>
+where
+    T: Copy,
+{
+    cell: &'a Atomic<T>,
+    value: T,
+}
+
+impl<'a, T> Deref for AtomicWriteGuard<'a, T>
+where
+    T: Copy,
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<'a, T> DerefMut for AtomicWriteGuard<'a, T>
+where
+    T: Copy,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+
+impl<'a, T> Drop for AtomicWriteGuard<'a, T>
+where
+    T: Copy,
+{
+    fn drop(&mut self) {
+        self.cell.publish(self.value);
+        self.cell.unlock_writer();
+    }
+}
@@ -0,0 +1,327 @@
+use std::{
+    cell::RefCell,
+    fmt::Display,
+    ops::{AddAssign, Deref, DerefMut},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    table::Column,
+    traits::{KeyValueMap, Lock},
+};
+
+use super::{Atomic, Mvcc, SpinLock};
+
+/// A [Lock] wrapper that records contention metrics for the lock it wraps.
+///
+/// Can be used as either the outer or inner lock of a [Column],
+/// in which case the `Column` derive will see through it to the wrapped lock.
+///
+/// Construction from a value is implemented for each lock type provided by this crate,
+/// since a blanket `From<T>` would overlap with `From<Instrumented<L>>`.
+/// Other locks can be wrapped with [Instrumented::new].
+#[derive(Debug, Default)]
+pub struct Instrumented<L> {
+    lock: L,
+    stats: LockStats,
+}
+
+impl<L> Instrumented<L> {
+    pub fn new(lock: L) -> Self {
+        Instrumented {
+            lock,
+            stats: Default::default(),
+        }
+    }
+
+    pub fn inner(&self) -> &L {
+        &self.lock
+    }
+
+    pub fn into_inner(self) -> L {
+        self.lock
+    }
+
+    /// Clear all recorded metrics.
+    pub fn reset(&self) {
+        self.stats.reset()
+    }
+}
+
+/// A type that can report the contention metrics it has recorded.
+pub trait Instrumentation {
+    fn report(&self) -> LockReport;
+}
+
+impl<L> Instrumentation for Instrumented<L> {
+    fn report(&self) -> LockReport {
+        self.stats.report()
+    }
+}
+
+impl<'a, T, L> Lock<'a, T> for Instrumented<L>
+where
+    L: Lock<'a, T> + 'a,
+    Self: From<T>,
+{
+    type ReadGuard = InstrumentedReadGuard<'a, L::ReadGuard>;
+    type WriteGuard = InstrumentedWriteGuard<'a, L::WriteGuard>;
+
+    fn read(&'a self) -> Self::ReadGuard {
+        let start = Instant::now();
+        let guard = self.lock.read();
+        let acquired = Instant::now();
+
+        self.stats.reads.fetch_add(1, Ordering::Relaxed);
+        add_duration(&self.stats.read_wait, acquired - start);
+
+        let readers = self.stats.readers.fetch_add(1, Ordering::Relaxed) + 1;
+        self.stats
+            .peak_readers
+            .fetch_max(readers, Ordering::Relaxed);
+
+        InstrumentedReadGuard {
+            guard,
+            stats: &self.stats,
+            acquired,
+        }
+    }
+
+    fn write(&'a self) -> Self::WriteGuard {
+        let start = Instant::now();
+        let guard = self.lock.write();
+        let acquired = Instant::now();
+
+        self.stats.writes.fetch_add(1, Ordering::Relaxed);
+        add_duration(&self.stats.write_wait, acquired - start);
+
+        InstrumentedWriteGuard {
+            guard,
+            stats: &self.stats,
+            acquired,
+        }
+    }
+    /// Not recorded, since it can't contend.
+    fn get_mut(&mut self) -> &mut T {
+        self.lock.get_mut()
+    }
+}
+
+macro_rules! impl_from_value {
+    ($($lock:ident)::+) => {
+        impl<T> From<T> for Instrumented<$($lock)::+<T>> {
+            fn from(value: T) -> Self {
+                Instrumented::new(value.into())
+            }
+        }
+    };
+}
+
+impl_from_value!(RefCell);
+impl_from_value!(Mutex);
+impl_from_value!(RwLock);
+impl_from_value!(Mvcc);
+impl_from_value!(SpinLock);
+
+impl<T> From<T> for Instrumented<Atomic<T>>
+where
+    T: Copy,
+{
+    fn from(value: T) -> Self {
+        Instrumented::new(value.into())
+    }
+}
+
+#[cfg(feature = "parking_lot")]
+impl_from_value!(parking_lot::Mutex);
+
+#[cfg(feature = "parking_lot")]
+impl_from_value!(parking_lot::RwLock);
+
+/// A read guard that records its hold duration on drop.
+pub struct InstrumentedReadGuard<'a, G> {
+    guard: G,
+    stats: &'a LockStats,
+    acquired: Instant,
+}
+
+impl<'a, G> Deref for InstrumentedReadGuard<'a, G>
+where
+    G: Deref,
+{
+    type Target = G::Target;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<'a, G> Drop for InstrumentedReadGuard<'a, G> {
+    fn drop(&mut self) {
+        add_duration(&self.stats.read_hold, self.acquired.elapsed());
+        self.stats.readers.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A write guard that records its hold duration on drop.
+pub struct InstrumentedWriteGuard<'a, G> {
+    guard: G,
+    stats: &'a LockStats,
+    acquired: Instant,
+}
+
+impl<'a, G> Deref for InstrumentedWriteGuard<'a, G>
+where
+    G: Deref,
+{
+    type Target = G::Target;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<'a, G> DerefMut for InstrumentedWriteGuard<'a, G>
+where
+    G: DerefMut,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+impl<'a, G> Drop for InstrumentedWriteGuard<'a, G> {
+    fn drop(&mut self) {
+        add_duration(&self.stats.write_hold, self.acquired.elapsed());
+    }
+}
+
+/// Atomic counters backing an [Instrumented] lock.
+#[derive(Debug, Default)]
+pub struct LockStats {
+    reads: AtomicU64,
+    writes: AtomicU64,
+    read_wait: AtomicU64,
+    write_wait: AtomicU64,
+    read_hold: AtomicU64,
+    write_hold: AtomicU64,
+    readers: AtomicUsize,
+    peak_readers: AtomicUsize,
+}
+
+impl LockStats {
+    fn report(&self) -> LockReport {
+        LockReport {
+            reads: self.reads.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+            read_wait: load_duration(&self.read_wait),
+            write_wait: load_duration(&self.write_wait),
+            read_hold: load_duration(&self.read_hold),
+            write_hold: load_duration(&self.write_hold),
+            peak_readers: self.peak_readers.load(Ordering::Relaxed),
+        }
+    }
+
+    fn reset(&self) {
+        for counter in [
+            &self.reads,
+            &self.writes,
+            &self.read_wait,
+            &self.write_wait,
+            &self.read_hold,
+            &self.write_hold,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+        self.peak_readers
+            .store(self.readers.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+}
+
+fn add_duration(counter: &AtomicU64, duration: Duration) {
+    counter.fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+}
+
+fn load_duration(counter: &AtomicU64) -> Duration {
+    Duration::from_nanos(counter.load(Ordering::Relaxed))
+}
+
+/// A snapshot of the metrics recorded by an [Instrumented] lock.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct LockReport {
+    /// Number of read guards acquired.
+    pub reads: u64,
+    /// Number of write guards acquired.
+    pub writes: u64,
+    /// Total time spent waiting to acquire read guards.
+    pub read_wait: Duration,
+    /// Total time spent waiting to acquire write guards.
+    pub write_wait: Duration,
+    /// Total time read guards were held.
+    pub read_hold: Duration,
+    /// Total time write guards were held.
+    pub write_hold: Duration,
+    /// The largest number of read guards held at once.
+    pub peak_readers: usize,
+}
+
+impl AddAssign for LockReport {
+    fn add_assign(&mut self, rhs: Self) {
+        self.reads += rhs.reads;
+        self.writes += rhs.writes;
+        self.read_wait += rhs.read_wait;
+        self.write_wait += rhs.write_wait;
+        self.read_hold += rhs.read_hold;
+        self.write_hold += rhs.write_hold;
+        self.peak_readers = self.peak_readers.max(rhs.peak_readers);
+    }
+}
+
+impl Display for LockReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} reads (waited {:?}, held {:?}, peak {} concurrent), {} writes (waited {:?}, held {:?})",
+            self.reads,
+            self.read_wait,
+            self.read_hold,
+            self.peak_readers,
+            self.writes,
+            self.write_wait,
+            self.write_hold,
+        )
+    }
+}
+
+/// Report the metrics recorded by a [Column]'s outer lock.
+pub fn outer_report<'a, Tbl, K, T>(tbl: &'a Tbl) -> LockReport
+where
+    Tbl: Column<'a, K, T>,
+    Tbl::OuterLock: Instrumentation,
+    K: 'a,
+{
+    tbl.outer_lock().report()
+}
+
+/// Report the combined metrics recorded by each of a cell map's inner locks.
+///
+/// Takes the cell map rather than the table, since the caller needs to hold its outer guard,
+/// i.e. `inner_report(&*Column::<usize, u32>::read_cell_map(&table))`.
+pub fn inner_report<'a, M, K, L>(cell_map: &'a M) -> LockReport
+where
+    M: KeyValueMap<'a, K, L>,
+    L: Instrumentation + 'a,
+    K: 'a,
+{
+    let mut report = LockReport::default();
+    for key in cell_map.keys() {
+        if let Some(cell) = cell_map.get(key) {
+            report += cell.report();
+        }
+    }
+    report
+}
@@ -0,0 +1,15 @@
+mod atomic;
+mod spin;
+
+#[cfg(feature = "std")]
+mod instrumented;
+#[cfg(feature = "std")]
+mod mvcc;
+
+pub use atomic::*;
+pub use spin::*;
+
+#[cfg(feature = "std")]
+pub use instrumented::*;
+#[cfg(feature = "std")]
+pub use mvcc::*;
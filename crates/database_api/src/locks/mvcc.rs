@@ -0,0 +1,217 @@
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use crate::traits::Lock;
+
+/// An immutable, numbered version of the data inside an [Mvcc] lock.
+#[derive(Debug, Clone)]
+pub struct Version<T> {
+    number: u64,
+    data: T,
+}
+
+/// A multi-version lock.
+///
+/// Reads return a [Snapshot] of the most recently published version, and never wait on writers.
+/// Writes copy the current version, and publish the copy as a new version when their guard drops,
+/// unless the thread is panicking, in which case the half-written copy is discarded.
+/// Writers are serialized with respect to each other, but never wait on readers.
+///
+/// Old versions are reclaimed once the last [Snapshot] referencing them is dropped.
+///
+/// Used as the outer lock of a [Column](crate::Column), this gives `read_columns`
+/// a consistent point-in-time view of the cell map while `write_columns` proceeds.
+///
+/// Cloning an [Mvcc] lock is cheap, as the new lock shares the current version until written.
+/// When the inner locks of a cell map are also [Mvcc], a writer's copy of the map therefore
+/// holds copies of its cells, and cells written through that copy are published with it,
+/// leaving the cells of earlier snapshots as they were.
+///
+/// Cells written through a read guard, as [Row::get_row](crate::Row::get_row) does,
+/// are written in place in the version that guard reads, so every snapshot of that version sees them,
+/// and a writer that copied the version beforehand publishes over them.
+/// Write cells through `write_columns` to version them along with the map.
+pub struct Mvcc<T> {
+    current: Mutex<Arc<Version<T>>>,
+    writer: Mutex<()>,
+}
+
+impl<T> Mvcc<T> {
+    /// The number of the most recently published version.
+    pub fn version(&self) -> u64 {
+        self.current().number
+    }
+
+    /// Take a snapshot of the most recently published version.
+    pub fn snapshot(&self) -> Snapshot<T> {
+        Snapshot(self.current())
+    }
+
+    fn current(&self) -> Arc<Version<T>> {
+        self.current.lock().expect("poisoned").clone()
+    }
+
+    fn publish(&self, version: Version<T>) {
+        *self.current.lock().expect("poisoned") = Arc::new(version);
+    }
+}
+
+impl<T> Default for Mvcc<T>
+where
+    T: Default,
+{
+    fn default() -> Self {
+        T::default().into()
+    }
+}
+
+impl<T> From<T> for Mvcc<T> {
+    fn from(data: T) -> Self {
+        Mvcc {
+            current: Mutex::new(Arc::new(Version { number: 0, data })),
+            writer: Default::default(),
+        }
+    }
+}
+
+impl<T> Clone for Mvcc<T> {
+    fn clone(&self) -> Self {
+        Mvcc {
+            current: Mutex::new(self.current()),
+            writer: Default::default(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Mvcc<T>
+where
+    T: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let current = self.current();
+        f.debug_struct("Mvcc")
+            .field("version", &current.number)
+            .field("data", &current.data)
+            .finish()
+    }
+}
+
+impl<'a, T> Lock<'a, T> for Mvcc<T>
+where
+    T: Clone + Default + 'a,
+{
+    type ReadGuard = Snapshot<T>;
+    type WriteGuard = MvccWriteGuard<'a, T>;
+
+    fn read(&'a self) -> Self::ReadGuard {
+        self.snapshot()
+    }
+
+    fn write(&'a self) -> Self::WriteGuard {
+        // A writer that panicked published nothing, so there's nothing to recover from
+        let writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let current = self.current();
+        MvccWriteGuard {
+            lock: self,
+            _writer: writer,
+            number: current.number + 1,
+            data: current.data.clone(),
+        }
+    }
+    /// Writes in place under a new version number, copying the current version first
+    /// if any [Snapshot] still holds it, so snapshots are left as they were.
+    fn get_mut(&mut self) -> &mut T {
+        let current = self
+            .current
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        let version = Arc::make_mut(current);
+        version.number += 1;
+        &mut version.data
+    }
+}
+
+/// A read guard over a published [Version] of an [Mvcc] lock.
+#[derive(Debug)]
+pub struct Snapshot<T>(Arc<Version<T>>);
+
+impl<T> Snapshot<T> {
+    /// The number of the version this snapshot was taken from.
+    pub fn version(&self) -> u64 {
+        self.0.number
+    }
+}
+
+impl<T> Clone for Snapshot<T> {
+    fn clone(&self) -> Self {
+        Snapshot(self.0.clone())
+    }
+}
+
+impl<T> Deref for Snapshot<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0.data
+    }
+}
+
+/// A write guard over a private copy of an [Mvcc] lock's data,
+/// which is published as a new [Version] on drop, or discarded if the thread is panicking.
+pub struct MvccWriteGuard<'a, T>
+where
+    T: Default,
+{
+    lock: &'a Mvcc<T>,
+    _writer: MutexGuard<'a, ()>,
+    number: u64,
+    data: T,
+}
+
+impl<'a, T> MvccWriteGuard<'a, T>
+where
+    T: Default,
+{
+    /// The number this guard's data will be published under.
+    pub fn version(&self) -> u64 {
+        self.number
+    }
+}
+
+impl<'a, T> Deref for MvccWriteGuard<'a, T>
+where
+    T: Default,
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
+impl<'a, T> DerefMut for MvccWriteGuard<'a, T>
+where
+    T: Default,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.data
+    }
+}
+
+impl<'a, T> Drop for MvccWriteGuard<'a, T>
+where
+    T: Default,
+{
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            return;
+        }
+
+        self.lock.publish(Version {
+            number: self.number,
+            data: std::mem::take(&mut self.data),
+        });
+    }
+}
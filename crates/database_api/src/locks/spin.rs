@@ -0,0 +1,144 @@
+use core::{
+    cell::UnsafeCell,
+    hint::spin_loop,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::traits::Lock;
+
+const WRITER: usize = !(usize::MAX >> 1);
+
+/// A reader-writer spin lock, for multi-core targets without `std::sync`.
+///
+/// Any number of readers or a single writer may hold the lock at once.
+/// Waiting is done by busy-looping, so guards should be held briefly.
+#[derive(Default)]
+pub struct SpinLock<T> {
+    state: AtomicUsize,
+    value: UnsafeCell<T>,
+}
+
+// SAFETY: Access to the value is synchronized through `state`.
+unsafe impl<T> Sync for SpinLock<T> where T: Send + Sync {}
+
+impl<T> SpinLock<T> {
+    pub const fn new(value: T) -> Self {
+        SpinLock {
+            state: AtomicUsize::new(0),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    pub fn read(&self) -> SpinReadGuard<'_, T> {
+        loop {
+            let state = self.state.load(Ordering::Relaxed);
+            if state & WRITER == 0
+                && self
+                    .state
+                    .compare_exchange_weak(state, state + 1, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                return SpinReadGuard(self);
+            }
+            spin_loop();
+        }
+    }
+
+    pub fn write(&self) -> SpinWriteGuard<'_, T> {
+        while self
+            .state
+            .compare_exchange_weak(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            spin_loop();
+        }
+        SpinWriteGuard(self)
+    }
+}
+
+impl<T> From<T> for SpinLock<T> {
+    fn from(value: T) -> Self {
+        SpinLock::new(value)
+    }
+}
+
+impl<T> core::fmt::Debug for SpinLock<T>
+where
+    T: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("SpinLock").field(&*self.read()).finish()
+    }
+}
+
+impl<'a, T> Lock<'a, T> for SpinLock<T>
+where
+    T: Default + 'a,
+{
+    type ReadGuard = SpinReadGuard<'a, T>;
+    type WriteGuard = SpinWriteGuard<'a, T>;
+
+    fn read(&'a self) -> Self::ReadGuard {
+        SpinLock::read(self)
+    }
+
+    fn write(&'a self) -> Self::WriteGuard {
+        SpinLock::write(self)
+    }
+
+    fn get_mut(&mut self) -> &mut T {
+        SpinLock::get_mut(self)
+    }
+}
+
+/// A shared guard over the value inside a [SpinLock].
+pub struct SpinReadGuard<'a, T>(&'a SpinLock<T>);
+
+impl<'a, T> Deref for SpinReadGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The reader count is nonzero, so no writer holds the lock.
+        unsafe { &*self.0.value.get() }
+    }
+}
+
+impl<'a, T> Drop for SpinReadGuard<'a, T> {
+    fn drop(&mut self) {
+        self.0.state.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// An exclusive guard over the value inside a [SpinLock].
+pub struct SpinWriteGuard<'a, T>(&'a SpinLock<T>);
+
+impl<'a, T> Deref for SpinWriteGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The writer bit is set, so this is the only guard.
+        unsafe { &*self.0.value.get() }
+    }
+}
+
+impl<'a, T> DerefMut for SpinWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: The writer bit is set, so this is the only guard.
+        unsafe { &mut *self.0.value.get() }
+    }
+}
+
+impl<'a, T> Drop for SpinWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        self.0.state.store(0, Ordering::Release);
+    }
+}
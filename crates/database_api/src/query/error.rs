@@ -0,0 +1,46 @@
+use alloc::string::String;
+use core::{fmt::Display, ops::Range};
+
+/// The reason a [Query](super::Query) failed to parse or run,
+/// along with the byte range of the offending token in the query text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    pub message: String,
+    pub span: Range<usize>,
+}
+
+impl QueryError {
+    pub fn new(message: impl Into<String>, span: Range<usize>) -> Self {
+        QueryError {
+            message: message.into(),
+            span,
+        }
+    }
+
+    /// Show the message beneath the query text, with the offending token underlined.
+    pub fn render(&self, query: &str) -> String {
+        let padding = query[..self.span.start].chars().count();
+        let width = query[self.span.clone()].chars().count().max(1);
+
+        let mut rendered = String::from(query);
+        rendered.push('\n');
+        rendered.extend(core::iter::repeat_n(' ', padding));
+        rendered.extend(core::iter::repeat_n('^', width));
+        rendered.push(' ');
+        rendered.push_str(&self.message);
+        rendered
+    }
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{} at {}..{}",
+            self.message, self.span.start, self.span.end
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for QueryError {}
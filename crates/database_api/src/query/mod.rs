@@ -0,0 +1,12 @@
+mod error;
+mod reflect;
+mod result;
+mod statement;
+mod token;
+mod value;
+
+pub use error::*;
+pub use reflect::*;
+pub use result::*;
+pub use statement::*;
+pub use value::*;
@@ -0,0 +1,26 @@
+use alloc::vec::Vec;
+
+use super::Value;
+
+/// Runtime access to the columns of a table, so a [Query](super::Query) can be run against it.
+///
+/// Generated by the Column derive for tables marked `#[database(reflect)]`,
+/// which requires the key and value types of every column to implement [ReflectValue](super::ReflectValue).
+pub trait Reflect {
+    /// The name of the table, as used in FROM clauses.
+    fn table_name(&self) -> &'static str;
+
+    /// The names of the table's columns, in declaration order.
+    fn column_names(&self) -> &'static [&'static str];
+
+    /// Read each key and value of several columns, or `None` if the table lacks any of them.
+    ///
+    /// Every requested column is read-locked, in declaration order, before any cells are copied out,
+    /// so the columns are read as one consistent snapshot.
+    fn read_columns(&self, columns: &[&str]) -> Option<Vec<Vec<(Value, Value)>>>;
+
+    /// Read each key and value of a column, or `None` if the table has no such column.
+    fn read_column(&self, column: &str) -> Option<Vec<(Value, Value)>> {
+        self.read_columns(&[column])?.pop()
+    }
+}
@@ -0,0 +1,91 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Display;
+
+use super::Value;
+
+/// The rows selected by a [Query](super::Query), each a key alongside the selected column values.
+///
+/// Displays as a text table, led by a `key` column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryResult {
+    columns: Vec<&'static str>,
+    rows: Vec<(Value, Vec<Value>)>,
+}
+
+impl QueryResult {
+    pub fn new(columns: Vec<&'static str>, rows: Vec<(Value, Vec<Value>)>) -> Self {
+        QueryResult { columns, rows }
+    }
+
+    /// The names of the selected columns, in the order they were selected.
+    pub fn columns(&self) -> &[&'static str] {
+        &self.columns
+    }
+
+    pub fn rows(&self) -> &[(Value, Vec<Value>)] {
+        &self.rows
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+}
+
+impl Display for QueryResult {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let header = core::iter::once("key".to_string())
+            .chain(self.columns.iter().map(|column| column.to_string()))
+            .collect::<Vec<_>>();
+        let rows = self
+            .rows
+            .iter()
+            .map(|(key, values)| {
+                core::iter::once(key)
+                    .chain(values)
+                    .map(|value| value.to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let widths = (0..header.len())
+            .map(|i| {
+                core::iter::once(&header)
+                    .chain(&rows)
+                    .map(|row| row[i].chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect::<Vec<_>>();
+
+        let write_row = |f: &mut core::fmt::Formatter<'_>, row: &[String]| {
+            for (i, (cell, width)) in row.iter().zip(&widths).enumerate() {
+                if i == row.len() - 1 {
+                    writeln!(f, "{}", cell)?;
+                } else {
+                    write!(f, "{:width$} | ", cell, width = width)?;
+                }
+            }
+            Ok(())
+        };
+
+        write_row(f, &header)?;
+        let rule = widths
+            .iter()
+            .map(|width| "-".repeat(*width))
+            .collect::<Vec<_>>()
+            .join("-+-");
+        writeln!(f, "{}", rule)?;
+        for row in rows.iter() {
+            write_row(f, row)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,516 @@
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::String,
+    vec::Vec,
+};
+use core::{cmp::Ordering, ops::Range, str::FromStr};
+
+use super::{
+    token::{tokenize, CompareOp, Keyword, Token, TokenKind},
+    QueryError, QueryResult, Reflect, Value,
+};
+
+/// A query over a table implementing [Reflect], written in a small subset of SQL:
+///
+/// ```text
+/// SELECT (* | column, ...) FROM table
+///     [WHERE condition]
+///     [ORDER BY column [ASC | DESC], ...]
+///     [LIMIT count]
+/// ```
+///
+/// Conditions compare columns and literals (numbers, `'text'`, `TRUE`, `FALSE` and `NULL`)
+/// using `=`, `!=`, `<`, `<=`, `>` and `>=`, or test a bool column on its own,
+/// and combine with `AND`, `OR`, `NOT` and parentheses.
+/// Comparisons against a null value are false, besides `= NULL` and `!= NULL`.
+///
+/// The rows of a query are the keys present in every column it selects, filters or orders by,
+/// read from one snapshot of those columns.
+#[derive(Debug, Clone)]
+pub struct Query {
+    columns: Option<Vec<Name>>,
+    table: Name,
+    filter: Option<Condition>,
+    order: Vec<(Name, Ordering)>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Name {
+    name: String,
+    span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Column(Name),
+    Literal(Value, Range<usize>),
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+    Compare {
+        lhs: Operand,
+        op: CompareOp,
+        rhs: Operand,
+        span: Range<usize>,
+    },
+    Test(Operand),
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Self, QueryError> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            position: 0,
+            depth: 0,
+        };
+
+        parser.expect_keyword(Keyword::Select)?;
+        let columns = if parser.peek().kind == TokenKind::Star {
+            parser.next();
+            None
+        } else {
+            Some(parser.names()?)
+        };
+
+        parser.expect_keyword(Keyword::From)?;
+        let table = parser.name("a table")?;
+
+        let filter = if parser.eat_keyword(Keyword::Where) {
+            Some(parser.or()?)
+        } else {
+            None
+        };
+
+        let mut order = Vec::new();
+        if parser.eat_keyword(Keyword::Order) {
+            parser.expect_keyword(Keyword::By)?;
+            loop {
+                let column = parser.name("a column")?;
+                let direction = if parser.eat_keyword(Keyword::Desc) {
+                    Ordering::Greater
+                } else {
+                    parser.eat_keyword(Keyword::Asc);
+                    Ordering::Less
+                };
+                order.push((column, direction));
+
+                if !parser.eat(&TokenKind::Comma) {
+                    break;
+                }
+            }
+        }
+
+        let limit = if parser.eat_keyword(Keyword::Limit) {
+            match parser.next() {
+                Token {
+                    kind: TokenKind::Literal(Value::UInt(limit)),
+                    ..
+                } => Some(limit as usize),
+                token => return Err(unexpected(&token, "a row count")),
+            }
+        } else {
+            None
+        };
+
+        let token = parser.next();
+        if token.kind != TokenKind::End {
+            return Err(unexpected(&token, "end of query"));
+        }
+
+        Ok(Query {
+            columns,
+            table,
+            filter,
+            order,
+            limit,
+        })
+    }
+
+    /// Run the query against whichever of `tables` it names.
+    pub fn run(&self, tables: &[&dyn Reflect]) -> Result<QueryResult, QueryError> {
+        let table = tables
+            .iter()
+            .find(|table| table.table_name() == self.table.name)
+            .ok_or_else(|| {
+                QueryError::new(
+                    format!("Unknown table `{}`", self.table.name),
+                    self.table.span.clone(),
+                )
+            })?;
+
+        // Every column the query mentions must exist
+        let column_names = table.column_names();
+        let column_index = |column: &Name| {
+            column_names
+                .iter()
+                .position(|name| *name == column.name)
+                .ok_or_else(|| {
+                    QueryError::new(
+                        format!(
+                            "Unknown column `{}` in table `{}`",
+                            column.name, self.table.name
+                        ),
+                        column.span.clone(),
+                    )
+                })
+        };
+
+        let selected = match &self.columns {
+            Some(columns) => columns
+                .iter()
+                .map(column_index)
+                .collect::<Result<Vec<_>, _>>()?,
+            None => (0..column_names.len()).collect(),
+        };
+
+        let order = self
+            .order
+            .iter()
+            .map(|(column, direction)| Ok((column_index(column)?, *direction)))
+            .collect::<Result<Vec<_>, QueryError>>()?;
+
+        let mut filtered = Vec::new();
+        if let Some(filter) = &self.filter {
+            filter.columns(&mut filtered);
+        }
+        let filtered = filtered
+            .into_iter()
+            .map(column_index)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Read only the columns the query uses, with rows indexed by their position in `read`
+        let read = selected
+            .iter()
+            .chain(filtered.iter())
+            .chain(order.iter().map(|(column, _)| column))
+            .copied()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let position = |column: usize| {
+            read.binary_search(&column)
+                .expect("Read columns include every used column")
+        };
+
+        let names = read.iter().map(|i| column_names[*i]).collect::<Vec<_>>();
+        let cells = table
+            .read_columns(&names)
+            .expect("Columns are checked")
+            .into_iter()
+            .map(|cells| cells.into_iter().collect::<BTreeMap<_, _>>())
+            .collect::<Vec<_>>();
+
+        // Rows are the keys every read column holds, visiting the smallest column's keys
+        let keys = cells
+            .iter()
+            .min_by_key(|cells| cells.len())
+            .into_iter()
+            .flat_map(|smallest| smallest.keys())
+            .filter(|key| cells.iter().all(|cells| cells.contains_key(key)));
+
+        let mut rows = Vec::new();
+        for key in keys {
+            let row = cells
+                .iter()
+                .map(|cells| cells[key].clone())
+                .collect::<Vec<_>>();
+
+            let matches = match &self.filter {
+                Some(filter) => filter.eval(&|column| {
+                    row[position(column_index(column).expect("Columns are checked"))].clone()
+                })?,
+                None => true,
+            };
+
+            if matches {
+                rows.push((key.clone(), row));
+            }
+        }
+
+        let order = order
+            .into_iter()
+            .map(|(column, direction)| (position(column), direction))
+            .collect::<Vec<_>>();
+
+        rows.sort_by(|(_, lhs), (_, rhs)| {
+            order
+                .iter()
+                .map(|(column, direction)| match direction {
+                    Ordering::Greater => rhs[*column].cmp(&lhs[*column]),
+                    _ => lhs[*column].cmp(&rhs[*column]),
+                })
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+
+        if let Some(limit) = self.limit {
+            rows.truncate(limit);
+        }
+
+        let rows = rows
+            .into_iter()
+            .map(|(key, row)| {
+                let row = selected.iter().map(|i| row[position(*i)].clone()).collect();
+                (key, row)
+            })
+            .collect();
+
+        Ok(QueryResult::new(
+            selected.iter().map(|i| column_names[*i]).collect(),
+            rows,
+        ))
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Query::parse(text)
+    }
+}
+
+impl Operand {
+    fn column(&self) -> Option<&Name> {
+        match self {
+            Operand::Column(column) => Some(column),
+            Operand::Literal(..) => None,
+        }
+    }
+
+    fn span(&self) -> Range<usize> {
+        match self {
+            Operand::Column(column) => column.span.clone(),
+            Operand::Literal(_, span) => span.clone(),
+        }
+    }
+
+    fn value(&self, row: &dyn Fn(&Name) -> Value) -> Value {
+        match self {
+            Operand::Column(column) => row(column),
+            Operand::Literal(value, _) => value.clone(),
+        }
+    }
+}
+
+impl Condition {
+    /// Collect every column mentioned by the condition, in order of appearance.
+    fn columns<'a>(&'a self, columns: &mut Vec<&'a Name>) {
+        match self {
+            Condition::And(lhs, rhs) | Condition::Or(lhs, rhs) => {
+                lhs.columns(columns);
+                rhs.columns(columns);
+            }
+            Condition::Not(condition) => condition.columns(columns),
+            Condition::Compare { lhs, rhs, .. } => {
+                columns.extend(lhs.column().into_iter().chain(rhs.column()));
+            }
+            Condition::Test(operand) => columns.extend(operand.column()),
+        }
+    }
+
+    fn eval(&self, row: &dyn Fn(&Name) -> Value) -> Result<bool, QueryError> {
+        Ok(match self {
+            Condition::And(lhs, rhs) => lhs.eval(row)? && rhs.eval(row)?,
+            Condition::Or(lhs, rhs) => lhs.eval(row)? || rhs.eval(row)?,
+            Condition::Not(condition) => !condition.eval(row)?,
+            Condition::Compare { lhs, op, rhs, span } => {
+                let (lhs, rhs) = (lhs.value(row), rhs.value(row));
+                if lhs.is_null() || rhs.is_null() {
+                    match op {
+                        CompareOp::Eq => lhs.is_null() && rhs.is_null(),
+                        CompareOp::Ne => lhs.is_null() != rhs.is_null(),
+                        _ => false,
+                    }
+                } else {
+                    let ordering = lhs.compare(&rhs).ok_or_else(|| {
+                        QueryError::new(
+                            format!("Cannot compare {} with {}", lhs.kind(), rhs.kind()),
+                            span.clone(),
+                        )
+                    })?;
+                    match op {
+                        CompareOp::Eq => ordering.is_eq(),
+                        CompareOp::Ne => ordering.is_ne(),
+                        CompareOp::Lt => ordering.is_lt(),
+                        CompareOp::Le => ordering.is_le(),
+                        CompareOp::Gt => ordering.is_gt(),
+                        CompareOp::Ge => ordering.is_ge(),
+                    }
+                }
+            }
+            Condition::Test(operand) => match operand.value(row) {
+                Value::Bool(value) => value,
+                Value::Null => false,
+                value => {
+                    return Err(QueryError::new(
+                        format!("Expected a bool condition, found {}", value.kind()),
+                        operand.span(),
+                    ))
+                }
+            },
+        })
+    }
+}
+
+fn unexpected(token: &Token, expected: &str) -> QueryError {
+    QueryError::new(
+        format!("Expected {}, found {}", expected, token.kind),
+        token.span.clone(),
+    )
+}
+
+/// How deeply conditions can nest through `NOT`, parentheses and chains of `AND` and `OR`,
+/// so that parsing and evaluating them can't overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+/// A recursive descent parser over the tokens of a query.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    /// The depth of the condition being parsed.
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position]
+    }
+
+    /// Take the next token, stopping at the end of the query.
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.position].clone();
+        if token.kind != TokenKind::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        let matches = self.peek().kind == *kind;
+        if matches {
+            self.next();
+        }
+        matches
+    }
+
+    fn eat_keyword(&mut self, keyword: Keyword) -> bool {
+        self.eat(&TokenKind::Keyword(keyword))
+    }
+
+    fn expect_keyword(&mut self, keyword: Keyword) -> Result<(), QueryError> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(unexpected(
+                self.peek(),
+                &format!("{}", TokenKind::Keyword(keyword)),
+            ))
+        }
+    }
+
+    fn name(&mut self, expected: &str) -> Result<Name, QueryError> {
+        match self.next() {
+            Token {
+                kind: TokenKind::Ident(name),
+                span,
+            } => Ok(Name { name, span }),
+            token => Err(unexpected(&token, expected)),
+        }
+    }
+
+    fn names(&mut self) -> Result<Vec<Name>, QueryError> {
+        let mut names = Vec::from([self.name("a column")?]);
+        while self.eat(&TokenKind::Comma) {
+            names.push(self.name("a column")?);
+        }
+        Ok(names)
+    }
+
+    /// Take the next token if it has the given kind, entering a deeper condition.
+    fn nest(&mut self, kind: &TokenKind) -> Result<bool, QueryError> {
+        if self.peek().kind != *kind {
+            return Ok(false);
+        }
+
+        let token = self.next();
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(QueryError::new(
+                format!("Conditions can't nest more than {} deep", MAX_DEPTH),
+                token.span,
+            ));
+        }
+        Ok(true)
+    }
+
+    fn or(&mut self) -> Result<Condition, QueryError> {
+        let depth = self.depth;
+        let mut condition = self.and()?;
+        // Each operator in a chain nests the conditions before it one level deeper
+        while self.nest(&TokenKind::Keyword(Keyword::Or))? {
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
+        }
+        self.depth = depth;
+        Ok(condition)
+    }
+
+    fn and(&mut self) -> Result<Condition, QueryError> {
+        let depth = self.depth;
+        let mut condition = self.not()?;
+        while self.nest(&TokenKind::Keyword(Keyword::And))? {
+            condition = Condition::And(Box::new(condition), Box::new(self.not()?));
+        }
+        self.depth = depth;
+        Ok(condition)
+    }
+
+    fn not(&mut self) -> Result<Condition, QueryError> {
+        if self.nest(&TokenKind::Keyword(Keyword::Not))? {
+            let condition = self.not()?;
+            self.depth -= 1;
+            return Ok(Condition::Not(Box::new(condition)));
+        }
+
+        if self.nest(&TokenKind::LParen)? {
+            let condition = self.or()?;
+            self.depth -= 1;
+            let token = self.next();
+            return match token.kind {
+                TokenKind::RParen => Ok(condition),
+                _ => Err(unexpected(&token, "`)`")),
+            };
+        }
+
+        let lhs = self.operand()?;
+        match self.peek().kind {
+            TokenKind::Compare(op) => {
+                let span = self.next().span;
+                let rhs = self.operand()?;
+                Ok(Condition::Compare { lhs, op, rhs, span })
+            }
+            _ => Ok(Condition::Test(lhs)),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand, QueryError> {
+        match self.next() {
+            Token {
+                kind: TokenKind::Ident(name),
+                span,
+            } => Ok(Operand::Column(Name { name, span })),
+            Token {
+                kind: TokenKind::Literal(value),
+                span,
+            } => Ok(Operand::Literal(value, span)),
+            token => Err(unexpected(&token, "a column or value")),
+        }
+    }
+}
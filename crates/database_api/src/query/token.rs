@@ -0,0 +1,219 @@
+use alloc::{format, string::String, vec::Vec};
+use core::{fmt::Display, iter::Peekable, ops::Range, str::CharIndices};
+
+use super::{QueryError, Value};
+
+/// Words with special meaning in a [Query](super::Query), matched case-insensitively.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Keyword {
+    Select,
+    From,
+    Where,
+    Order,
+    By,
+    Asc,
+    Desc,
+    Limit,
+    And,
+    Or,
+    Not,
+}
+
+const KEYWORDS: &[(&str, Keyword)] = &[
+    ("SELECT", Keyword::Select),
+    ("FROM", Keyword::From),
+    ("WHERE", Keyword::Where),
+    ("ORDER", Keyword::Order),
+    ("BY", Keyword::By),
+    ("ASC", Keyword::Asc),
+    ("DESC", Keyword::Desc),
+    ("LIMIT", Keyword::Limit),
+    ("AND", Keyword::And),
+    ("OR", Keyword::Or),
+    ("NOT", Keyword::Not),
+];
+
+/// The comparison operators usable in a WHERE clause.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Keyword(Keyword),
+    Ident(String),
+    Literal(Value),
+    Compare(CompareOp),
+    Comma,
+    Star,
+    LParen,
+    RParen,
+    End,
+}
+
+impl Display for TokenKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            TokenKind::Keyword(keyword) => {
+                let (name, _) = KEYWORDS.iter().find(|(_, k)| k == keyword).unwrap();
+                f.write_str(name)
+            }
+            TokenKind::Ident(ident) => write!(f, "`{}`", ident),
+            TokenKind::Literal(Value::Text(text)) => write!(f, "'{}'", text),
+            TokenKind::Literal(value) => write!(f, "{}", value),
+            TokenKind::Compare(op) => f.write_str(match op {
+                CompareOp::Eq => "=",
+                CompareOp::Ne => "!=",
+                CompareOp::Lt => "<",
+                CompareOp::Le => "<=",
+                CompareOp::Gt => ">",
+                CompareOp::Ge => ">=",
+            }),
+            TokenKind::Comma => f.write_str(","),
+            TokenKind::Star => f.write_str("*"),
+            TokenKind::LParen => f.write_str("("),
+            TokenKind::RParen => f.write_str(")"),
+            TokenKind::End => f.write_str("end of query"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Range<usize>,
+}
+
+/// Split query text into tokens, ending with [TokenKind::End].
+pub fn tokenize(text: &str) -> Result<Vec<Token>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let (kind, end) = if c.is_alphabetic() || c == '_' {
+            let end = take_while(text, &mut chars, |c| c.is_alphanumeric() || c == '_');
+            (word_kind(&text[start..end]), end)
+        } else if c.is_ascii_digit() || (c == '-' && next_is_digit(text, start)) {
+            chars.next();
+            let end = take_while(text, &mut chars, |c| c.is_ascii_digit() || c == '.');
+            let number = &text[start..end];
+            let value = if number.contains('.') {
+                number.parse().ok().map(Value::Float)
+            } else if number.starts_with('-') {
+                number.parse().ok().map(Value::Int)
+            } else {
+                number.parse().ok().map(Value::UInt)
+            };
+            let value = value.ok_or_else(|| {
+                QueryError::new(format!("Invalid number `{}`", number), start..end)
+            })?;
+            (TokenKind::Literal(value), end)
+        } else if c == '\'' {
+            chars.next();
+            let mut string = String::new();
+            let end = loop {
+                match chars.next() {
+                    // Quotes are escaped by doubling them
+                    Some((_, '\'')) if matches!(chars.peek(), Some((_, '\''))) => {
+                        chars.next();
+                        string.push('\'');
+                    }
+                    Some((end, '\'')) => break end + 1,
+                    Some((_, c)) => string.push(c),
+                    None => return Err(QueryError::new("Unterminated string", start..text.len())),
+                }
+            };
+            (TokenKind::Literal(Value::Text(string)), end)
+        } else {
+            chars.next();
+            let next = chars.peek().map(|&(_, c)| c);
+            let (kind, len) = match (c, next) {
+                ('=', Some('=')) => (TokenKind::Compare(CompareOp::Eq), 2),
+                ('!', Some('=')) | ('<', Some('>')) => (TokenKind::Compare(CompareOp::Ne), 2),
+                ('<', Some('=')) => (TokenKind::Compare(CompareOp::Le), 2),
+                ('>', Some('=')) => (TokenKind::Compare(CompareOp::Ge), 2),
+                ('=', _) => (TokenKind::Compare(CompareOp::Eq), 1),
+                ('<', _) => (TokenKind::Compare(CompareOp::Lt), 1),
+                ('>', _) => (TokenKind::Compare(CompareOp::Gt), 1),
+                (',', _) => (TokenKind::Comma, 1),
+                ('*', _) => (TokenKind::Star, 1),
+                ('(', _) => (TokenKind::LParen, 1),
+                (')', _) => (TokenKind::RParen, 1),
+                _ => {
+                    return Err(QueryError::new(
+                        format!("Unexpected character `{}`", c),
+                        start..start + c.len_utf8(),
+                    ))
+                }
+            };
+            if len == 2 {
+                chars.next();
+            }
+            (kind, start + len)
+        };
+
+        tokens.push(Token {
+            kind,
+            span: start..end,
+        });
+    }
+
+    tokens.push(Token {
+        kind: TokenKind::End,
+        span: text.len()..text.len(),
+    });
+
+    Ok(tokens)
+}
+
+/// Consume characters while they match, returning the end of the token.
+fn take_while(
+    text: &str,
+    chars: &mut Peekable<CharIndices<'_>>,
+    predicate: impl Fn(char) -> bool,
+) -> usize {
+    while let Some(&(_, c)) = chars.peek() {
+        if !predicate(c) {
+            break;
+        }
+        chars.next();
+    }
+    chars.peek().map_or(text.len(), |&(end, _)| end)
+}
+
+fn next_is_digit(text: &str, start: usize) -> bool {
+    text[start + 1..]
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_digit())
+}
+
+fn word_kind(word: &str) -> TokenKind {
+    if let Some((_, keyword)) = KEYWORDS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(word))
+    {
+        return TokenKind::Keyword(*keyword);
+    }
+
+    if word.eq_ignore_ascii_case("TRUE") {
+        TokenKind::Literal(Value::Bool(true))
+    } else if word.eq_ignore_ascii_case("FALSE") {
+        TokenKind::Literal(Value::Bool(false))
+    } else if word.eq_ignore_ascii_case("NULL") {
+        TokenKind::Literal(Value::Null)
+    } else {
+        TokenKind::Ident(word.into())
+    }
+}
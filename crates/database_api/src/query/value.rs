@@ -0,0 +1,243 @@
+use alloc::{
+    borrow::{Cow, ToOwned},
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{cmp::Ordering, fmt::Display};
+
+use crate::{ForeignKey, GenerationalKey};
+
+/// A key or column value read through [Reflect](super::Reflect), or a literal in a [Query](super::Query).
+///
+/// Values are totally ordered: nulls first, then booleans, numbers, text and tuples.
+/// Numbers of different types compare by value, with integers ordered before equal floats.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Text(String),
+    Tuple(Vec<Value>),
+}
+
+impl Value {
+    /// A name for the kind of value, for error messages.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "bool",
+            Value::Int(_) | Value::UInt(_) => "integer",
+            Value::Float(_) => "float",
+            Value::Text(_) => "text",
+            Value::Tuple(_) => "tuple",
+        }
+    }
+
+    /// Compare two values of the same kind, treating integers and floats as one kind.
+    ///
+    /// Returns `None` for values of different kinds, or if either is null.
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        if self.is_null() || other.is_null() || self.rank() != other.rank() {
+            return None;
+        }
+
+        Some(self.cmp(other))
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Value::Null => 0,
+            Value::Bool(_) => 1,
+            Value::Int(_) | Value::UInt(_) | Value::Float(_) => 2,
+            Value::Text(_) => 3,
+            Value::Tuple(_) => 4,
+        }
+    }
+
+    fn integer(&self) -> Option<i128> {
+        match self {
+            Value::Int(value) => Some(*value as i128),
+            Value::UInt(value) => Some(*value as i128),
+            _ => None,
+        }
+    }
+
+    fn float(&self) -> Option<f64> {
+        match self {
+            Value::Int(value) => Some(*value as f64),
+            Value::UInt(value) => Some(*value as f64),
+            Value::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Value {}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Value::Bool(lhs), Value::Bool(rhs)) => lhs.cmp(rhs),
+            (Value::Text(lhs), Value::Text(rhs)) => lhs.cmp(rhs),
+            (Value::Tuple(lhs), Value::Tuple(rhs)) => lhs.cmp(rhs),
+            (lhs, rhs) if lhs.rank() == 2 && rhs.rank() == 2 => {
+                match (lhs.integer(), rhs.integer()) {
+                    (Some(lhs), Some(rhs)) => lhs.cmp(&rhs),
+                    _ => lhs
+                        .float()
+                        .unwrap()
+                        .total_cmp(&rhs.float().unwrap())
+                        .then_with(|| lhs.integer().is_none().cmp(&rhs.integer().is_none())),
+                }
+            }
+            (lhs, rhs) => lhs.rank().cmp(&rhs.rank()),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Value::Null => f.write_str("NULL"),
+            Value::Bool(value) => Display::fmt(value, f),
+            Value::Int(value) => Display::fmt(value, f),
+            Value::UInt(value) => Display::fmt(value, f),
+            Value::Float(value) => Display::fmt(value, f),
+            Value::Text(value) => f.pad(value),
+            Value::Tuple(values) => {
+                f.write_str("(")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    Display::fmt(value, f)?;
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+/// Keys and column values that can be read through [Reflect](super::Reflect).
+pub trait ReflectValue {
+    fn to_value(&self) -> Value;
+}
+
+macro_rules! impl_reflect_value {
+    ($variant:ident($as:ty): $($ty:ty),*) => {
+        $(
+            impl ReflectValue for $ty {
+                fn to_value(&self) -> Value {
+                    Value::$variant(*self as $as)
+                }
+            }
+        )*
+    };
+}
+
+impl_reflect_value!(Int(i64): i8, i16, i32, i64, isize);
+impl_reflect_value!(UInt(u64): u8, u16, u32, u64, usize);
+impl_reflect_value!(Float(f64): f32, f64);
+
+impl ReflectValue for bool {
+    fn to_value(&self) -> Value {
+        Value::Bool(*self)
+    }
+}
+
+impl ReflectValue for char {
+    fn to_value(&self) -> Value {
+        Value::Text(self.to_string())
+    }
+}
+
+impl ReflectValue for str {
+    fn to_value(&self) -> Value {
+        Value::Text(self.to_owned())
+    }
+}
+
+impl ReflectValue for String {
+    fn to_value(&self) -> Value {
+        Value::Text(self.clone())
+    }
+}
+
+impl<T> ReflectValue for &T
+where
+    T: ReflectValue + ?Sized,
+{
+    fn to_value(&self) -> Value {
+        (**self).to_value()
+    }
+}
+
+impl<T> ReflectValue for Cow<'_, T>
+where
+    T: ReflectValue + ToOwned + ?Sized,
+{
+    fn to_value(&self) -> Value {
+        (**self).to_value()
+    }
+}
+
+impl<T> ReflectValue for Option<T>
+where
+    T: ReflectValue,
+{
+    fn to_value(&self) -> Value {
+        self.as_ref().map_or(Value::Null, ReflectValue::to_value)
+    }
+}
+
+impl<K> ReflectValue for ForeignKey<K>
+where
+    K: ReflectValue,
+{
+    fn to_value(&self) -> Value {
+        self.0.to_value()
+    }
+}
+
+impl ReflectValue for GenerationalKey {
+    fn to_value(&self) -> Value {
+        Value::Text(self.to_string())
+    }
+}
+
+macro_rules! impl_reflect_tuple {
+    ($($ty:ident),*) => {
+        impl<$($ty),*> ReflectValue for ($($ty,)*)
+        where
+            $($ty: ReflectValue),*
+        {
+            #[allow(non_snake_case)]
+            fn to_value(&self) -> Value {
+                let ($($ty,)*) = self;
+                Value::Tuple(alloc::vec![$($ty.to_value()),*])
+            }
+        }
+    };
+}
+
+impl_reflect_tuple!(A, B);
+impl_reflect_tuple!(A, B, C);
+impl_reflect_tuple!(A, B, C, D);
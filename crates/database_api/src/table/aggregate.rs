@@ -0,0 +1,213 @@
+use alloc::collections::BTreeMap;
+use core::marker::PhantomData;
+
+use crate::traits::{KeyValueMap, Lock};
+
+use super::{CellMap, Keys, Row};
+
+/// Iterates over the keys and read-locked values of a [CellMap], as returned by [CellMap::cells].
+pub struct Cells<'a, K, L, V, M>
+where
+    K: 'a,
+    L: Lock<'a, V> + 'a,
+    M: KeyValueMap<'a, K, L>,
+{
+    map: &'a M,
+    keys: M::Keys,
+    _phantom: PhantomData<fn() -> V>,
+}
+
+impl<'a, K, L, V, M> Cells<'a, K, L, V, M>
+where
+    K: 'a,
+    L: Lock<'a, V> + 'a,
+    M: KeyValueMap<'a, K, L>,
+{
+    pub fn new(map: &'a M) -> Self {
+        Cells {
+            map,
+            keys: map.keys(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a, K, L, V, M> Iterator for Cells<'a, K, L, V, M>
+where
+    K: 'a,
+    L: Lock<'a, V> + 'a,
+    M: KeyValueMap<'a, K, L>,
+{
+    type Item = (&'a K, L::ReadGuard);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for key in self.keys.by_ref() {
+            if let Some(cell) = CellMap::read_cell(self.map, key) {
+                return Some((key, cell));
+            }
+        }
+
+        None
+    }
+}
+
+/// Iterates over the keys and inner guards of a [Row], as returned by [Row::rows].
+///
+/// Keys missing from any of the row's columns are skipped.
+pub struct Rows<'a, Tbl, K, R>
+where
+    Tbl: Keys<'a, K>,
+    K: 'a,
+    R: Row<'a, Tbl, K>,
+    R::Insert: 'static,
+{
+    tbl: &'a Tbl,
+    read_columns: &'a R::OuterReadGuards,
+    keys: Tbl::Keys,
+}
+
+impl<'a, Tbl, K, R> Rows<'a, Tbl, K, R>
+where
+    Tbl: Keys<'a, K>,
+    K: 'a,
+    R: Row<'a, Tbl, K>,
+    R::Insert: 'static,
+{
+    pub fn new(tbl: &'a Tbl, read_columns: &'a R::OuterReadGuards) -> Self {
+        Rows {
+            tbl,
+            read_columns,
+            keys: R::keys(tbl),
+        }
+    }
+}
+
+impl<'a, Tbl, K, R> Iterator for Rows<'a, Tbl, K, R>
+where
+    Tbl: Keys<'a, K>,
+    K: 'a,
+    R: Row<'a, Tbl, K>,
+    R::Insert: 'static,
+{
+    type Item = (K, R::InnerGuards);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for key in self.keys.by_ref() {
+            if let Ok(row) = R::try_get_row(self.tbl, self.read_columns, &key) {
+                return Some((key, row));
+            }
+        }
+
+        None
+    }
+}
+
+/// Aggregations over an iterator of values, such as the cells of a column from [CellMap::cells]
+/// or the rows of a [Row] from [Row::rows].
+///
+/// Complements [Iterator::count], [Iterator::sum], [Iterator::min], [Iterator::max] and [Iterator::fold]
+/// with versions that accept floating point values, and grouping.
+pub trait Aggregate: Iterator + Sized {
+    /// The smallest value, skipping values that can't be compared with themselves such as NaN.
+    ///
+    /// Returns the first of several equal minimums.
+    fn partial_min(self) -> Option<Self::Item>
+    where
+        Self::Item: PartialOrd,
+    {
+        self.fold(None, |min, value| {
+            if value.partial_cmp(&value).is_none() {
+                return min;
+            }
+
+            match min {
+                Some(min) if value >= min => Some(min),
+                _ => Some(value),
+            }
+        })
+    }
+
+    /// The largest value, skipping values that can't be compared with themselves such as NaN.
+    ///
+    /// Returns the first of several equal maximums.
+    fn partial_max(self) -> Option<Self::Item>
+    where
+        Self::Item: PartialOrd,
+    {
+        self.fold(None, |max, value| {
+            if value.partial_cmp(&value).is_none() {
+                return max;
+            }
+
+            match max {
+                Some(max) if value <= max => Some(max),
+                _ => Some(value),
+            }
+        })
+    }
+
+    /// The arithmetic mean of the values, or `None` if there are none.
+    fn mean(self) -> Option<f64>
+    where
+        Self::Item: Mean,
+    {
+        let (sum, count) = self.fold((0.0, 0usize), |(sum, count), value| {
+            (sum + value.to_f64(), count + 1)
+        });
+
+        if count == 0 {
+            None
+        } else {
+            Some(sum / count as f64)
+        }
+    }
+
+    /// Fold the values into one accumulator per group, starting each from `init`.
+    ///
+    /// Values for which `group` returns `None`, such as keys missing from a grouping column, are skipped.
+    fn group_by<G, B>(
+        self,
+        mut group: impl FnMut(&Self::Item) -> Option<G>,
+        mut init: impl FnMut() -> B,
+        mut fold: impl FnMut(B, Self::Item) -> B,
+    ) -> BTreeMap<G, B>
+    where
+        G: Ord,
+    {
+        let mut groups = BTreeMap::new();
+        for value in self {
+            let key = match group(&value) {
+                Some(key) => key,
+                None => continue,
+            };
+
+            let acc = groups.remove(&key).unwrap_or_else(&mut init);
+            groups.insert(key, fold(acc, value));
+        }
+        groups
+    }
+}
+
+impl<I> Aggregate for I where I: Iterator {}
+
+/// A numeric value that can be averaged by [Aggregate::mean].
+///
+/// Unlike `Into<f64>`, covers 64-bit and pointer-sized integers such as counters,
+/// which round to the nearest representable float.
+pub trait Mean {
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! impl_mean {
+    ($($ty:ty),*) => {
+        $(
+            impl Mean for $ty {
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+            }
+        )*
+    };
+}
+
+impl_mean!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
@@ -0,0 +1,75 @@
+use core::ops::Deref;
+
+use crate::traits::Lock;
+
+/// How a [Column](super::Column) reaches the values stored as cells in its cell map.
+///
+/// Implemented by [Locked], for cells that are inner locks around their value,
+/// and [Bare], for two-level columns whose cells are the values themselves.
+pub trait CellAccess<'a, C, V>
+where
+    C: 'a,
+{
+    type ReadGuard: Deref<Target = V>;
+
+    /// Guard over a cell written through a shared reference to its cell map, as [Row::get_row](super::Row::get_row) does.
+    ///
+    /// Bare values can't be written that way, so [Bare] hands out `&V`, which won't convert
+    /// into a row's `&mut` field. Such rows are fetched with [Row::get_row_mut](super::Row::get_row_mut).
+    type WriteGuard: Deref<Target = V>;
+
+    fn read(cell: &'a C) -> Self::ReadGuard;
+    fn write(cell: &'a C) -> Self::WriteGuard;
+
+    /// Borrow the value of a cell through exclusive access to its cell map.
+    fn get_mut(cell: &mut C) -> &mut V;
+}
+
+/// [CellAccess] for cells that are inner locks, i.e. `RwLock<BTreeMap<usize, RwLock<u32>>>`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Locked;
+
+impl<'a, L, V> CellAccess<'a, L, V> for Locked
+where
+    L: Lock<'a, V> + 'a,
+{
+    type ReadGuard = L::ReadGuard;
+    type WriteGuard = L::WriteGuard;
+
+    fn read(cell: &'a L) -> Self::ReadGuard {
+        cell.read()
+    }
+
+    fn write(cell: &'a L) -> Self::WriteGuard {
+        cell.write()
+    }
+
+    fn get_mut(cell: &mut L) -> &mut V {
+        cell.get_mut()
+    }
+}
+
+/// [CellAccess] for two-level columns, i.e. `RefCell<BTreeMap<usize, u32>>`,
+/// which lend out their values straight from the outer guard.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Bare;
+
+impl<'a, V> CellAccess<'a, V, V> for Bare
+where
+    V: 'a,
+{
+    type ReadGuard = &'a V;
+    type WriteGuard = &'a V;
+
+    fn read(cell: &'a V) -> Self::ReadGuard {
+        cell
+    }
+
+    fn write(cell: &'a V) -> Self::WriteGuard {
+        cell
+    }
+
+    fn get_mut(cell: &mut V) -> &mut V {
+        cell
+    }
+}
@@ -1,4 +1,6 @@
-use crate::traits::{KeyValueMap, Lock};
+use crate::traits::{KeyError, KeyValueMap, Lock};
+
+use super::Cells;
 
 /// A [KeyValueMap] type containing a [`Lock`] value type
 pub trait CellMap<'a, K, L, V>: KeyValueMap<'a, K, L>
@@ -16,6 +18,22 @@ where
         cell.map(Lock::write)
     }
 
+    fn try_read_cell(&'a self, key: &K) -> Result<<L as Lock<'a, V>>::ReadGuard, KeyError> {
+        self.lookup(key).map(Lock::read)
+    }
+
+    fn try_write_cell(&'a self, key: &K) -> Result<<L as Lock<'a, V>>::WriteGuard, KeyError> {
+        self.lookup(key).map(Lock::write)
+    }
+
+    /// Iterate over each key and its read-locked value, for use with [Aggregate](super::Aggregate).
+    fn cells(&'a self) -> Cells<'a, K, L, V, Self>
+    where
+        Self: Sized,
+    {
+        Cells::new(self)
+    }
+
     fn insert(&mut self, key: K, value: V) -> Option<L>
     where
         L: From<V>,
@@ -23,7 +41,17 @@ where
         KeyValueMap::insert(self, key, value.into())
     }
 
-    fn extend(&mut self, values: impl Iterator<Item = (K, V)>) {
+    fn try_insert(&mut self, key: K, value: V) -> Result<Option<L>, KeyError>
+    where
+        L: From<V>,
+    {
+        KeyValueMap::try_insert(self, key, value.into())
+    }
+
+    fn extend(&mut self, values: impl Iterator<Item = (K, V)>)
+    where
+        L: From<V>,
+    {
         KeyValueMap::extend(self, values.map(|(key, value)| (key, value.into())))
     }
 
@@ -39,4 +67,3 @@ where
     L: Lock<'a, V> + 'a,
 {
 }
-
@@ -1,23 +1,31 @@
-use crate::traits::Lock;
+use crate::traits::{KeyValueMap, MapLock};
 
-use super::CellMap;
+use super::CellAccess;
 
-/// A type with OuterLock<CellMap<Key, InnerLock<Value>>> structure.
-pub trait Column<'a, K, T>
+/// A type with OuterLock<CellMap<Key, InnerLock<Value>>> structure,
+/// or OuterLock<CellMap<Key, Value>> for two-level columns.
+///
+/// `C` selects the column, and is either the value type itself,
+/// or a marker type naming the column when several share a value type.
+pub trait Column<'a, K, C>
 where
     K: 'a,
 {
-    type OuterLock: Lock<'a, Self::CellMap> + 'a;
-    type CellMap: CellMap<'a, K, Self::InnerLock, T> + 'a;
-    type InnerLock: Lock<'a, T> + 'a;
+    type Value: 'a;
+    type OuterLock: MapLock<'a, Self::CellMap> + 'a;
+    type CellMap: KeyValueMap<'a, K, Self::InnerLock> + 'a;
+    /// The cells of the cell map, which are the values themselves in two-level columns.
+    type InnerLock: From<Self::Value> + 'a;
+    /// [Locked](super::Locked), or [Bare](super::Bare) for two-level columns.
+    type Access: CellAccess<'a, Self::InnerLock, Self::Value>;
 
     fn outer_lock(&'a self) -> &'a Self::OuterLock;
 
-    fn read_cell_map(&'a self) -> <Self::OuterLock as Lock<'a, Self::CellMap>>::ReadGuard {
+    fn read_cell_map(&'a self) -> <Self::OuterLock as MapLock<'a, Self::CellMap>>::ReadGuard {
         self.outer_lock().read()
     }
 
-    fn write_cell_map(&'a self) -> <Self::OuterLock as Lock<'a, Self::CellMap>>::WriteGuard {
+    fn write_cell_map(&'a self) -> <Self::OuterLock as MapLock<'a, Self::CellMap>>::WriteGuard {
         self.outer_lock().write()
     }
 }
@@ -0,0 +1,152 @@
+use core::marker::PhantomData;
+
+use super::{GenerationalKey, NextKey};
+
+/// A type that can be used as a component of a [CompositeKey].
+///
+/// Provides the smallest and largest values of the type,
+/// which bound the keys sharing a given prefix.
+pub trait KeyComponent: Ord + Clone {
+    const MIN: Self;
+    const MAX: Self;
+}
+
+macro_rules! impl_key_component {
+    ($($ty:ty: $min:expr, $max:expr);* $(;)?) => {
+        $(
+            impl KeyComponent for $ty {
+                const MIN: Self = $min;
+                const MAX: Self = $max;
+            }
+        )*
+    };
+}
+
+impl_key_component!(
+    (): (), ();
+    bool: false, true;
+    char: '\0', char::MAX;
+    u8: u8::MIN, u8::MAX;
+    u16: u16::MIN, u16::MAX;
+    u32: u32::MIN, u32::MAX;
+    u64: u64::MIN, u64::MAX;
+    u128: u128::MIN, u128::MAX;
+    usize: usize::MIN, usize::MAX;
+    i8: i8::MIN, i8::MAX;
+    i16: i16::MIN, i16::MAX;
+    i32: i32::MIN, i32::MAX;
+    i64: i64::MIN, i64::MAX;
+    i128: i128::MIN, i128::MAX;
+    isize: isize::MIN, isize::MAX;
+    GenerationalKey: GenerationalKey::new(usize::MIN, u32::MIN), GenerationalKey::new(usize::MAX, u32::MAX);
+);
+
+/// A primary key made up of several components, ordered lexicographically.
+///
+/// Implemented for tuples of two to four components.
+pub trait CompositeKey: Ord + Clone {
+    type First: Ord + Clone;
+
+    fn first(&self) -> &Self::First;
+
+    /// The smallest and largest keys starting with `first`, inclusive.
+    fn prefix_bounds(first: Self::First) -> (Self, Self);
+}
+
+macro_rules! impl_composite_key {
+    ($($rest:ident),*) => {
+        impl<A, $($rest),*> CompositeKey for (A, $($rest),*)
+        where
+            A: Ord + Clone,
+            $($rest: KeyComponent),*
+        {
+            type First = A;
+
+            fn first(&self) -> &A {
+                &self.0
+            }
+
+            fn prefix_bounds(first: A) -> (Self, Self) {
+                (
+                    (first.clone(), $($rest::MIN),*),
+                    (first, $($rest::MAX),*),
+                )
+            }
+        }
+    };
+}
+
+impl_composite_key!(B);
+impl_composite_key!(B, C);
+impl_composite_key!(B, C, D);
+
+/// Builds a [CompositeKey] one component at a time.
+///
+/// ```
+/// use database_api::KeyBuilder;
+///
+/// let key = KeyBuilder::new(3usize).then(7u32).build();
+/// assert_eq!(key, (3, 7));
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KeyBuilder<K>(K);
+
+impl<A> KeyBuilder<(A,)> {
+    pub fn new(first: A) -> Self {
+        KeyBuilder((first,))
+    }
+
+    pub fn then<B>(self, b: B) -> KeyBuilder<(A, B)> {
+        let (a,) = self.0;
+        KeyBuilder((a, b))
+    }
+}
+
+impl<A, B> KeyBuilder<(A, B)> {
+    pub fn then<C>(self, c: C) -> KeyBuilder<(A, B, C)> {
+        let (a, b) = self.0;
+        KeyBuilder((a, b, c))
+    }
+
+    pub fn build(self) -> (A, B) {
+        self.0
+    }
+}
+
+impl<A, B, C> KeyBuilder<(A, B, C)> {
+    pub fn then<D>(self, d: D) -> KeyBuilder<(A, B, C, D)> {
+        let (a, b, c) = self.0;
+        KeyBuilder((a, b, c, d))
+    }
+
+    pub fn build(self) -> (A, B, C) {
+        self.0
+    }
+}
+
+impl<A, B, C, D> KeyBuilder<(A, B, C, D)> {
+    pub fn build(self) -> (A, B, C, D) {
+        self.0
+    }
+}
+
+/// A [NextKey] implementation for tables whose keys are always supplied by the caller,
+/// such as relations keyed by the pair of entities they relate.
+///
+/// # Panics
+///
+/// [NextKey::next_key] panics, as there is no key to generate.
+#[derive(Debug)]
+pub struct ManualKeys<K>(PhantomData<fn() -> K>);
+
+impl<K> Default for ManualKeys<K> {
+    fn default() -> Self {
+        ManualKeys(PhantomData)
+    }
+}
+
+impl<K> NextKey<K> for ManualKeys<K> {
+    fn next_key(&self) -> K {
+        panic!("Keys of this table must be supplied by the caller")
+    }
+}
@@ -0,0 +1,85 @@
+use alloc::{boxed::Box, vec::Vec};
+use core::any::Any;
+
+/// A table that can remove a key from every column at once, implemented by the `Table` derive.
+pub trait Despawn<K> {
+    fn despawn(&self, key: &K) -> Despawned<K>;
+}
+
+/// The cells removed from every column of a table by its derived `despawn` method.
+///
+/// Cells are type-erased, each held as the column's inner lock under the column's field name.
+/// Two-level columns, whose maps store bare values, hold the value itself.
+pub struct Despawned<K> {
+    key: K,
+    cells: Vec<(&'static str, Box<dyn Any>)>,
+}
+
+impl<K> Despawned<K> {
+    pub fn new(key: K) -> Self {
+        Despawned {
+            key,
+            cells: Vec::new(),
+        }
+    }
+
+    /// Record the cell removed from `column`.
+    pub fn insert(&mut self, column: &'static str, cell: Box<dyn Any>) {
+        self.cells.push((column, cell));
+    }
+
+    /// The key that was despawned.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// The names of the columns that held a cell for the key, in declaration order.
+    pub fn columns(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.cells.iter().map(|(column, _)| *column)
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Returns true if no column held a cell for the key.
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Borrow the cell removed from `column`, if it held one of type `L`.
+    pub fn get<L>(&self, column: &str) -> Option<&L>
+    where
+        L: 'static,
+    {
+        self.cells
+            .iter()
+            .find(|(name, _)| *name == column)
+            .and_then(|(_, cell)| cell.downcast_ref())
+    }
+
+    /// Take the cell removed from `column`, if it held one of type `L`.
+    pub fn take<L>(&mut self, column: &str) -> Option<L>
+    where
+        L: 'static,
+    {
+        let index = self
+            .cells
+            .iter()
+            .position(|(name, cell)| *name == column && cell.is::<L>())?;
+        let (_, cell) = self.cells.remove(index);
+        cell.downcast().ok().map(|cell| *cell)
+    }
+}
+
+impl<K> core::fmt::Debug for Despawned<K>
+where
+    K: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Despawned")
+            .field("key", &self.key)
+            .field("columns", &self.columns().collect::<Vec<_>>())
+            .finish()
+    }
+}
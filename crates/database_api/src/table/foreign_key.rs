@@ -0,0 +1,236 @@
+use alloc::vec::Vec;
+use core::{
+    fmt::{Debug, Display},
+    marker::PhantomData,
+    ops::DerefMut,
+};
+
+use crate::traits::{KeyValueMap, Lock};
+
+use super::{CellMap, Column, Despawn, Event, Keys, Row};
+
+/// A column value referencing the key of a row in another table.
+///
+/// Insert and remove through a [Reference] to keep references from dangling.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ForeignKey<K>(pub K);
+
+/// A column value that may hold a [ForeignKey].
+pub trait ForeignKeyValue<K> {
+    fn foreign_key(&self) -> Option<&K>;
+
+    /// The value left behind by [OnRemove::SetNull], if the column is nullable.
+    fn null() -> Option<Self>
+    where
+        Self: Sized;
+}
+
+impl<K> ForeignKeyValue<K> for ForeignKey<K> {
+    fn foreign_key(&self) -> Option<&K> {
+        Some(&self.0)
+    }
+
+    fn null() -> Option<Self> {
+        None
+    }
+}
+
+impl<K> ForeignKeyValue<K> for Option<ForeignKey<K>> {
+    fn foreign_key(&self) -> Option<&K> {
+        self.as_ref().map(|foreign_key| &foreign_key.0)
+    }
+
+    fn null() -> Option<Self> {
+        Some(None)
+    }
+}
+
+/// What happens to referencing rows when the row they reference is removed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OnRemove {
+    /// Refuse to remove a row that is still referenced.
+    Restrict,
+    /// Remove the referencing rows along with it.
+    Cascade,
+    /// Clear the references, which requires an `Option<ForeignKey<K>>` column.
+    SetNull,
+}
+
+/// The reason a [Reference] refused an operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReferenceError<PK, CK> {
+    /// The referenced key is not present in the parent table.
+    MissingParent(PK),
+    /// The parent row is still referenced by a child row under [OnRemove::Restrict].
+    Restricted { parent: PK, child: CK },
+}
+
+impl<PK, CK> Display for ReferenceError<PK, CK>
+where
+    PK: Debug,
+    CK: Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ReferenceError::MissingParent(key) => write!(f, "Parent key {:?} is not present", key),
+            ReferenceError::Restricted { parent, child } => write!(
+                f,
+                "Parent key {:?} is still referenced by child key {:?}",
+                parent, child
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<PK, CK> std::error::Error for ReferenceError<PK, CK>
+where
+    PK: Debug,
+    CK: Debug,
+{
+}
+
+/// Enforces a [ForeignKey] column `C` of a child table, referencing the rows `P` of a parent table.
+///
+/// Child rows `CR` pair the foreign key with the rest of their values as `(key, values)`,
+/// usually by flattening another row alongside the key column,
+/// so that [Reference::insert] can check the key before inserting.
+///
+/// Inserts and removes take the parent's write guards, then the child's,
+/// so neither can race a concurrent remove or insert through the same reference,
+/// and two references over the same tables can't deadlock each other.
+/// Other guards over either table's columns must not be held across a call.
+/// Only rows inserted and removed through the reference are checked.
+/// Cascades despawn the referencing rows, but go only one level deep;
+/// chain references by removing children through their own [Reference].
+pub struct Reference<'a, PTbl, PK, P, CTbl, CK, CR, C> {
+    parent: &'a PTbl,
+    child: &'a CTbl,
+    on_remove: OnRemove,
+    _phantom: PhantomData<ReferenceTypes<PK, P, CK, CR, C>>,
+}
+
+type ReferenceTypes<PK, P, CK, CR, C> = fn() -> (PK, P, CK, CR, C);
+
+impl<'a, PTbl, PK, P, CTbl, CK, CR, C, V, I, M, L, G, W> Reference<'a, PTbl, PK, P, CTbl, CK, CR, C>
+where
+    PTbl: Keys<'a, PK>,
+    PK: PartialEq + Clone + 'a,
+    P: Row<'a, PTbl, PK>,
+    P::Insert: 'static,
+    CTbl: Keys<'a, CK>
+        + Despawn<CK>
+        + for<'b> Column<'b, CK, C, Value = V, CellMap = M, InnerLock = L>,
+    CK: Clone + 'static,
+    M: for<'b> KeyValueMap<'b, CK, L>,
+    L: for<'b> Lock<'b, V>,
+    CR: Row<'a, CTbl, CK, Insert = (V, I), OuterWriteGuards = (G, W)>,
+    G: DerefMut<Target = M>,
+    V: ForeignKeyValue<PK> + 'static,
+    I: 'static,
+{
+    /// Panics if `on_remove` is [OnRemove::SetNull] and the column isn't nullable.
+    pub fn new(parent: &'a PTbl, child: &'a CTbl, on_remove: OnRemove) -> Self {
+        assert!(
+            on_remove != OnRemove::SetNull || V::null().is_some(),
+            "OnRemove::SetNull requires an Option<ForeignKey<K>> column"
+        );
+
+        Reference {
+            parent,
+            child,
+            on_remove,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Check that `value` references a row present in the parent table, or nothing at all.
+    ///
+    /// Takes the parent's write guards, so the row can't be removed until they're dropped.
+    pub fn check(
+        &self,
+        parent_columns: &P::OuterWriteGuards,
+        value: &V,
+    ) -> Result<(), ReferenceError<PK, CK>> {
+        match value.foreign_key() {
+            Some(key) if !P::contains(parent_columns, key) => {
+                Err(ReferenceError::MissingParent(key.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Insert a child row, provided its foreign key references a row in the parent table.
+    ///
+    /// Write-locks the parent's columns, then the child's, until the row is inserted.
+    pub fn insert(
+        &self,
+        key: CK,
+        values: impl Into<(V, I)>,
+    ) -> Result<CR::Result, ReferenceError<PK, CK>> {
+        let parent_columns = P::write_columns(self.parent);
+        let values = values.into();
+        self.check(&parent_columns, &values.0)?;
+
+        let mut columns = CR::write_columns(self.child);
+        Ok(CR::insert(self.child, &mut columns, key, values))
+    }
+
+    /// The keys of child rows referencing the parent row `key`.
+    ///
+    /// Scans the whole foreign key column.
+    pub fn referencing(&self, key: &PK) -> Vec<CK> {
+        Self::referencing_in(&*Column::<CK, C>::read_cell_map(self.child), key)
+    }
+
+    fn referencing_in(cells: &M, key: &PK) -> Vec<CK> {
+        KeyValueMap::keys(cells)
+            .filter(|child| {
+                CellMap::read_cell(cells, child)
+                    .is_some_and(|value| value.foreign_key() == Some(key))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Remove a parent row, applying the [OnRemove] policy to any child rows referencing it.
+    ///
+    /// Write-locks the parent's columns, then the child's, until the parent row is gone.
+    /// Cascades release the child's columns before despawning each referencing row,
+    /// which takes every column of the child table.
+    pub fn remove(&self, key: &PK) -> Result<P::Result, ReferenceError<PK, CK>> {
+        let mut columns = P::write_columns(self.parent);
+        if !P::contains(&columns, key) {
+            return Err(ReferenceError::MissingParent(key.clone()));
+        }
+
+        let child_columns = CR::write_columns(self.child);
+        let children = Self::referencing_in(&child_columns.0, key);
+        match self.on_remove {
+            OnRemove::Restrict => {
+                if let Some(child) = children.into_iter().next() {
+                    return Err(ReferenceError::Restricted {
+                        parent: key.clone(),
+                        child,
+                    });
+                }
+            }
+            OnRemove::Cascade => {
+                drop(child_columns);
+                for child in children.iter() {
+                    self.child.despawn(child);
+                }
+            }
+            OnRemove::SetNull => {
+                for child in children.iter() {
+                    if let Some(mut value) = CellMap::write_cell(&*child_columns.0, child) {
+                        *value = V::null().unwrap();
+                    }
+                    self.child.publish(Event::Update(child.clone()));
+                }
+            }
+        }
+
+        Ok(P::remove(self.parent, &mut columns, key))
+    }
+}
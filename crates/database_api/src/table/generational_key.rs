@@ -0,0 +1,112 @@
+use alloc::vec::Vec;
+
+use crate::locks::SpinLock;
+
+use super::NextKey;
+
+/// A primary key made up of a recyclable index and the generation it was handed out in.
+///
+/// Once a key is freed, its index may be handed out again with a higher generation,
+/// so old handles can be told apart from the entity that replaced them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GenerationalKey {
+    index: usize,
+    generation: u32,
+}
+
+impl GenerationalKey {
+    pub const fn new(index: usize, generation: u32) -> Self {
+        GenerationalKey { index, generation }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+/// A key made up of a recyclable index and a generation, such as [GenerationalKey],
+/// that can be stored in a [GenerationalMap](crate::GenerationalMap).
+pub trait Generational: Copy + Eq {
+    fn index(&self) -> usize;
+    fn generation(&self) -> u32;
+}
+
+impl Generational for GenerationalKey {
+    fn index(&self) -> usize {
+        self.index
+    }
+
+    fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+impl core::fmt::Display for GenerationalKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Generations {
+    current: Vec<u32>,
+    free: Vec<usize>,
+}
+
+/// A [NextKey] implementation that hands out [GenerationalKey]s,
+/// recycling the indices of freed keys under a new generation.
+#[derive(Debug, Default)]
+pub struct GenerationalKeys {
+    generations: SpinLock<Generations>,
+}
+
+impl GenerationalKeys {
+    /// The number of keys currently in use.
+    pub fn len(&self) -> usize {
+        let generations = self.generations.read();
+        generations.current.len() - generations.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl NextKey<GenerationalKey> for GenerationalKeys {
+    fn next_key(&self) -> GenerationalKey {
+        let mut generations = self.generations.write();
+        match generations.free.pop() {
+            Some(index) => GenerationalKey::new(index, generations.current[index]),
+            None => {
+                generations.current.push(0);
+                GenerationalKey::new(generations.current.len() - 1, 0)
+            }
+        }
+    }
+
+    fn free_key(&self, key: &GenerationalKey) {
+        let mut generations = self.generations.write();
+        let generation = match generations.current.get_mut(key.index) {
+            Some(generation) if *generation == key.generation => generation,
+            _ => return,
+        };
+
+        *generation = generation.wrapping_add(1);
+        generations.free.push(key.index);
+    }
+
+    /// Keys whose index was never handed out aren't stale, just missing.
+    ///
+    /// Generations wrap around, so any key whose generation isn't the current one
+    /// for its index counts as stale.
+    fn is_stale(&self, key: &GenerationalKey) -> bool {
+        match self.generations.read().current.get(key.index) {
+            Some(generation) => key.generation != *generation,
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use alloc::collections::BTreeSet;
+use core::ops::Deref;
+
+use super::{ForeignKeyValue, Keys, Row};
+
+/// Which left rows a [Join] yields.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum JoinKind {
+    /// Only left rows with a matching right row.
+    Inner,
+    /// Every left row, paired with `None` where there is no matching right row.
+    Left,
+}
+
+/// Pairs the rows `L` of one table with the rows `R` of another,
+/// borrowing outer read guards taken with [Row::read_columns] for both.
+///
+/// Joins yield each left row's inner guards alongside those of its match,
+/// which is always `Some` for [JoinKind::Inner].
+/// Rows matched more than once are fetched once per match,
+/// so joined rows with write access should be dropped before fetching the next.
+/// Joins borrow the key caches they iterate, like [Row::keys], until they're dropped.
+pub struct Join<'a, LTbl, LK, L, RTbl, RK, R>
+where
+    LTbl: Keys<'a, LK>,
+    LK: 'a,
+    L: Row<'a, LTbl, LK>,
+    L::Insert: 'static,
+    RTbl: Keys<'a, RK>,
+    RK: 'a,
+    R: Row<'a, RTbl, RK>,
+    R::Insert: 'static,
+{
+    left: &'a LTbl,
+    left_columns: &'a L::OuterReadGuards,
+    right: &'a RTbl,
+    right_columns: &'a R::OuterReadGuards,
+    kind: JoinKind,
+}
+
+impl<'a, LTbl, LK, L, RTbl, RK, R> Join<'a, LTbl, LK, L, RTbl, RK, R>
+where
+    LTbl: Keys<'a, LK>,
+    LK: 'a,
+    L: Row<'a, LTbl, LK>,
+    L::Insert: 'static,
+    RTbl: Keys<'a, RK>,
+    RK: 'a,
+    R: Row<'a, RTbl, RK>,
+    R::Insert: 'static,
+{
+    pub fn new(
+        left: &'a LTbl,
+        left_columns: &'a L::OuterReadGuards,
+        right: &'a RTbl,
+        right_columns: &'a R::OuterReadGuards,
+        kind: JoinKind,
+    ) -> Self {
+        Join {
+            left,
+            left_columns,
+            right,
+            right_columns,
+            kind,
+        }
+    }
+}
+
+impl<'a, LTbl, K, L, RTbl, R> Join<'a, LTbl, K, L, RTbl, K, R>
+where
+    LTbl: Keys<'a, K>,
+    K: 'a,
+    L: Row<'a, LTbl, K>,
+    L::Insert: 'static,
+    RTbl: Keys<'a, K>,
+    R: Row<'a, RTbl, K>,
+    R::Insert: 'static,
+{
+    /// Pair rows stored under the same key in both tables.
+    ///
+    /// Inner joins visit the keys of whichever row caches fewer of them.
+    pub fn on_key(self) -> impl Iterator<Item = (L::InnerGuards, Option<R::InnerGuards>)> + 'a {
+        let Join {
+            left,
+            left_columns,
+            right,
+            right_columns,
+            kind,
+        } = self;
+
+        let (left_keys, right_keys) =
+            if kind == JoinKind::Inner && R::key_count(right) < L::key_count(left) {
+                (None, Some(R::keys(right)))
+            } else {
+                (Some(L::keys(left)), None)
+            };
+        let keys = left_keys
+            .into_iter()
+            .flatten()
+            .chain(right_keys.into_iter().flatten());
+
+        keys.filter_map(move |key| {
+            let left_row = L::try_get_row(left, left_columns, &key).ok()?;
+            let right_row = R::try_get_row(right, right_columns, &key).ok();
+            match (kind, right_row) {
+                (JoinKind::Inner, None) => None,
+                (_, right_row) => Some((left_row, right_row)),
+            }
+        })
+    }
+}
+
+impl<'a, LTbl, LK, L, RTbl, RK, R, G, J, V> Join<'a, LTbl, LK, L, RTbl, RK, R>
+where
+    LTbl: Keys<'a, LK>,
+    LK: 'a,
+    L: Row<'a, LTbl, LK, InnerGuards = (G, J)>,
+    L::Insert: 'static,
+    RTbl: Keys<'a, RK>,
+    RK: Clone + 'a,
+    R: Row<'a, RTbl, RK>,
+    R::Insert: 'static,
+    G: Deref<Target = V>,
+    V: ForeignKeyValue<RK>,
+{
+    /// Pair each left row with the right row referenced by its foreign key.
+    ///
+    /// Left rows pair their foreign key with the rest of their values, as for a [Reference](super::Reference),
+    /// and are always visited in full to read it.
+    pub fn on_left_foreign_key(
+        self,
+    ) -> impl Iterator<Item = (L::InnerGuards, Option<R::InnerGuards>)> + 'a {
+        let Join {
+            left,
+            left_columns,
+            right,
+            right_columns,
+            kind,
+        } = self;
+
+        L::keys(left).filter_map(move |key| {
+            let left_row = L::try_get_row(left, left_columns, &key).ok()?;
+            let right_key = left_row.0.foreign_key().cloned();
+            let right_row =
+                right_key.and_then(|key| R::try_get_row(right, right_columns, &key).ok());
+            match (kind, right_row) {
+                (JoinKind::Inner, None) => None,
+                (_, right_row) => Some((left_row, right_row)),
+            }
+        })
+    }
+}
+
+impl<'a, LTbl, LK, L, RTbl, RK, R, G, J, V> Join<'a, LTbl, LK, L, RTbl, RK, R>
+where
+    LTbl: Keys<'a, LK>,
+    LK: Ord + Clone + 'a,
+    L: Row<'a, LTbl, LK>,
+    L::Insert: 'static,
+    RTbl: Keys<'a, RK>,
+    RK: 'a,
+    R: Row<'a, RTbl, RK, InnerGuards = (G, J)>,
+    R::Insert: 'static,
+    G: Deref<Target = V>,
+    V: ForeignKeyValue<LK>,
+{
+    /// Pair each left row with every right row whose foreign key references it.
+    ///
+    /// Right rows pair their foreign key with the rest of their values, as for a [Reference](super::Reference),
+    /// and drive the join, so matches are yielded in right key order.
+    /// Left joins then yield the unreferenced left rows.
+    pub fn on_right_foreign_key(
+        self,
+    ) -> impl Iterator<Item = (L::InnerGuards, Option<R::InnerGuards>)> + 'a {
+        let Join {
+            left,
+            left_columns,
+            right,
+            right_columns,
+            kind,
+        } = self;
+
+        let mut right_keys = R::keys(right);
+        let mut unmatched = match kind {
+            JoinKind::Inner => None,
+            JoinKind::Left => Some(L::keys(left)),
+        };
+        let mut matched = BTreeSet::new();
+
+        core::iter::from_fn(move || {
+            for key in right_keys.by_ref() {
+                let right_row = match R::try_get_row(right, right_columns, &key) {
+                    Ok(right_row) => right_row,
+                    Err(_) => continue,
+                };
+
+                let left_key = match right_row.0.foreign_key() {
+                    Some(left_key) => left_key.clone(),
+                    None => continue,
+                };
+
+                if let Ok(left_row) = L::try_get_row(left, left_columns, &left_key) {
+                    if unmatched.is_some() {
+                        matched.insert(left_key);
+                    }
+                    return Some((left_row, Some(right_row)));
+                }
+            }
+
+            for key in unmatched.as_mut()?.by_ref() {
+                if matched.contains(&key) {
+                    continue;
+                }
+
+                if let Ok(left_row) = L::try_get_row(left, left_columns, &key) {
+                    return Some((left_row, None));
+                }
+            }
+
+            None
+        })
+    }
+}
@@ -1,14 +1,187 @@
-use std::any::TypeId;
+use alloc::{boxed::Box, vec::Vec};
+use core::{any::TypeId, marker::PhantomData, ops::Deref, ptr::NonNull};
+
+use crate::traits::{KeySet, KeyValueMap, Lock};
+
+use super::Event;
 
 /// A type that can cache sets of keys by [TypeId].
 pub trait Keys<'a, K>
 where
     K: 'a,
 {
+    /// Iterator over a cached key set.
+    ///
+    /// May hold read guards over the cache for as long as it lives,
+    /// so the cache should not be written to until it is dropped.
     type Keys: Iterator<Item = K>;
 
     fn insert_key(&'a self, type_id: TypeId, key: K);
     fn extend_keys(&'a self, type_id: TypeId, keys: impl Iterator<Item = K>);
     fn remove_key(&'a self, type_id: &TypeId, key: &K);
     fn keys(&'a self, type_id: &TypeId) -> Self::Keys;
+
+    /// Free `key` for recycling if no cached key set holds it any more.
+    ///
+    /// Does nothing by default; tables deriving [Keys] with a primary key
+    /// pass it to [NextKey::free_key](super::NextKey::free_key).
+    fn release_key(&'a self, _key: &K) {}
+
+    /// Returns true if `key` was handed out and has since been freed.
+    ///
+    /// Never by default; tables deriving [Keys] with a primary key
+    /// ask [NextKey::is_stale](super::NextKey::is_stale).
+    fn is_stale_key(&'a self, _key: &K) -> bool {
+        false
+    }
+
+    /// Queue `event` for the table's subscribers.
+    ///
+    /// Does nothing by default; tables deriving [Keys] with a `#[subscribers]` field
+    /// pass it to [Subscribers::publish](super::Subscribers::publish).
+    fn publish(&'a self, _event: Event<K>) {}
+
+    /// Copy out a cached key set, releasing the cache before returning.
+    ///
+    /// Use this over [Keys::keys] when the cache will be modified during iteration,
+    /// such as when inserting or removing rows.
+    fn snapshot_keys(&'a self, type_id: &TypeId) -> Vec<K> {
+        self.keys(type_id).collect()
+    }
+
+    /// The number of keys in a cached key set.
+    ///
+    /// Counts by iterating by default; tables deriving [Keys] ask the set directly.
+    fn key_count(&'a self, type_id: &TypeId) -> usize {
+        self.keys(type_id).count()
+    }
+
+    /// Returns true if the cached key set holds `key`.
+    ///
+    /// Scans the set by default; tables deriving [Keys] look the key up directly.
+    fn contains_key(&'a self, type_id: &TypeId, key: &K) -> bool
+    where
+        K: PartialEq,
+    {
+        self.keys(type_id).any(|cached| cached == *key)
+    }
+}
+
+/// A [Keys::Keys] iterator over a key cache with OuterLock<Map<TypeId, InnerLock<Set<Key>>>> structure,
+/// holding read guards over both locks while it iterates.
+///
+/// The iterator borrows from the guards it owns, which safe Rust can't express,
+/// so the guards are kept on the heap where moving the iterator doesn't move them.
+/// Keys are cloned out of the set, so no reference into either guard outlives the iterator.
+/// The unsafe code here is covered by `test_cached_keys`, which is meant to be run under Miri.
+pub struct CachedKeys<'a, K, S, OG, IG>
+where
+    K: 'a,
+    S: KeySet<'a, K> + 'a,
+{
+    // Borrows from the guards below, so Drop clears it before freeing them.
+    iter: Option<S::Iter>,
+    // Leaked boxes, freed only on drop. They're held as raw pointers rather than boxes,
+    // since moving a box would assert unique access to a guard the iterator borrows from.
+    inner_guard: Option<NonNull<IG>>,
+    outer_guard: NonNull<OG>,
+    _phantom: PhantomData<(Box<OG>, Box<IG>)>,
+}
+
+impl<'a, K, S, OG, IG> CachedKeys<'a, K, S, OG, IG>
+where
+    K: 'a,
+    S: KeySet<'a, K> + 'a,
+{
+    pub fn new<OL, M, IL>(outer_lock: &'a OL, type_id: &TypeId) -> Self
+    where
+        OL: Lock<'a, M, ReadGuard = OG>,
+        M: KeyValueMap<'a, TypeId, IL> + 'a,
+        IL: Lock<'a, S, ReadGuard = IG> + 'a,
+        OG: Deref<Target = M>,
+        IG: Deref<Target = S>,
+    {
+        // Built before borrowing through the guards, so they're released if that panics
+        let mut keys = CachedKeys {
+            iter: None,
+            inner_guard: None,
+            outer_guard: NonNull::from(Box::leak(Box::new(outer_lock.read()))),
+            _phantom: PhantomData,
+        };
+
+        // SAFETY: The pointer comes from a live leaked box, which stays at the same address
+        // until Drop frees it. The map is only reached through shared references,
+        // and everything borrowing it - the inner guard and the set iterator - is dropped
+        // before the box is. The 'a lifetime is never handed out: keys are cloned on the way out.
+        let map = unsafe { &*(&**keys.outer_guard.as_ptr() as *const M) };
+
+        if let Some(inner_lock) = map.get(type_id) {
+            let inner_guard = NonNull::from(Box::leak(Box::new(inner_lock.read())));
+            keys.inner_guard = Some(inner_guard);
+
+            // SAFETY: As for the map. The set iterator is dropped before the inner guard,
+            // which is freed before the outer guard it borrows from.
+            let set = unsafe { &*(&**inner_guard.as_ptr() as *const S) };
+            keys.iter = Some(set.iter());
+        }
+
+        keys
+    }
+}
+
+impl<'a, K, S, OG, IG> Drop for CachedKeys<'a, K, S, OG, IG>
+where
+    K: 'a,
+    S: KeySet<'a, K> + 'a,
+{
+    fn drop(&mut self) {
+        self.iter = None;
+
+        // SAFETY: Both pointers were leaked from boxes in CachedKeys::new and are freed only here,
+        // innermost first. Nothing borrows from them now that the iterator is gone,
+        // and the map reference taken in CachedKeys::new didn't escape it.
+        unsafe {
+            if let Some(inner_guard) = self.inner_guard.take() {
+                drop(Box::from_raw(inner_guard.as_ptr()));
+            }
+            drop(Box::from_raw(self.outer_guard.as_ptr()));
+        }
+    }
+}
+
+// SAFETY: The iterator owns its guards as a Box would, and drops them wherever it is dropped,
+// so it can move to another thread exactly when the guards and the set iterator can.
+// Guards that must be released on the thread that took them, such as std's, aren't Send.
+unsafe impl<'a, K, S, OG, IG> Send for CachedKeys<'a, K, S, OG, IG>
+where
+    K: 'a,
+    S: KeySet<'a, K> + 'a,
+    S::Iter: Send,
+    OG: Send,
+    IG: Send,
+{
+}
+
+// SAFETY: A shared reference to the iterator can't reach the guards or advance the iterator,
+// as every method that does takes &mut self, so it is Sync whenever its fields are.
+unsafe impl<'a, K, S, OG, IG> Sync for CachedKeys<'a, K, S, OG, IG>
+where
+    K: 'a,
+    S: KeySet<'a, K> + 'a,
+    S::Iter: Sync,
+    OG: Sync,
+    IG: Sync,
+{
+}
+
+impl<'a, K, S, OG, IG> Iterator for CachedKeys<'a, K, S, OG, IG>
+where
+    K: Clone + 'a,
+    S: KeySet<'a, K> + 'a,
+{
+    type Item = K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.as_mut()?.next().cloned()
+    }
 }
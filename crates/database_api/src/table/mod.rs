@@ -1,13 +1,33 @@
+mod aggregate;
+mod cell_access;
 mod cell_map;
 mod column;
+mod composite_key;
+mod despawn;
+mod foreign_key;
+mod generational_key;
+mod join;
 mod row;
 mod next_key;
 mod keys;
+mod transaction;
+mod subscription;
+mod view;
 pub mod from_row;
 
+pub use aggregate::*;
+pub use cell_access::*;
 pub use cell_map::*;
 pub use column::*;
+pub use composite_key::*;
+pub use despawn::*;
+pub use foreign_key::*;
+pub use generational_key::*;
+pub use join::*;
 pub use row::*;
 pub use next_key::*;
 pub use keys::*;
+pub use transaction::*;
+pub use subscription::*;
+pub use view::*;
 pub use from_row::*;
\ No newline at end of file
@@ -1,10 +1,39 @@
-use std::marker::PhantomData;
+use core::{marker::PhantomData, sync::atomic::Ordering};
 
 /// A type that can provide an incrementing primary key.
 pub trait NextKey<K> {
     fn next_key(&self) -> K;
+
+    /// Release a key that is no longer in use, so that it may be recycled.
+    fn free_key(&self, _key: &K) {}
+
+    /// Returns true if a key was handed out and has since been freed.
+    fn is_stale(&self, _key: &K) -> bool {
+        false
+    }
 }
 
+macro_rules! impl_next_key_atomic {
+    ($($size:literal: $atomic:ident => $key:ty),*) => {
+        $(
+            #[cfg(target_has_atomic = $size)]
+            impl NextKey<$key> for core::sync::atomic::$atomic {
+                fn next_key(&self) -> $key {
+                    self.fetch_add(1, Ordering::Relaxed)
+                }
+            }
+        )*
+    };
+}
+
+impl_next_key_atomic!(
+    "8": AtomicU8 => u8,
+    "16": AtomicU16 => u16,
+    "32": AtomicU32 => u32,
+    "64": AtomicU64 => u64,
+    "ptr": AtomicUsize => usize
+);
+
 /// An iterator that, given a reference to a [PrimaryKey] type, can provide an infinite stream of primary keys.
 pub struct NextKeyIterator<'a, T, K>
 where
@@ -1,9 +1,13 @@
-use super::{Keys, NextKey};
+use alloc::vec::Vec;
+
+use crate::traits::KeyError;
+
+use super::{Event, Keys, Rows};
 
 /// A type used to read/write sets of [Column]s
 pub trait Row<'a, Tbl, K>: Sized
 where
-    Tbl: Keys<'a, K> + NextKey<K>,
+    Tbl: Keys<'a, K>,
     K: 'a,
     <Self as Row<'a, Tbl, K>>::Insert: 'static,
 {
@@ -14,11 +18,39 @@ where
     type OuterWriteGuards;
     type InnerGuards;
 
-    fn keys(tbl: &'a Tbl) -> <Tbl as Keys<K>>::Keys
+    /// The values of a row borrowed by [Row::get_row_mut], for as long as `'b` borrows the write guards.
+    type InnerRefs<'b>
+    where
+        'a: 'b,
+        Tbl: 'a;
+
+    /// Iterate over the keys of this row, borrowing the table's key cache.
+    ///
+    /// Rows can't be inserted or removed until the iterator is dropped;
+    /// use [Row::snapshot_keys] to do so while visiting each key.
+    fn keys(tbl: &'a Tbl) -> <Tbl as Keys<'a, K>>::Keys
     where
         Tbl: Keys<'a, K>,
     {
-        tbl.keys(&std::any::TypeId::of::<Self::Insert>())
+        tbl.keys(&core::any::TypeId::of::<Self::Insert>())
+    }
+
+    /// Copy out the keys of this row, leaving the table's key cache free to be modified.
+    fn snapshot_keys(tbl: &'a Tbl) -> Vec<K> {
+        tbl.snapshot_keys(&core::any::TypeId::of::<Self::Insert>())
+    }
+
+    /// The number of keys cached for this row.
+    fn key_count(tbl: &'a Tbl) -> usize {
+        tbl.key_count(&core::any::TypeId::of::<Self::Insert>())
+    }
+
+    /// Returns true if `key` is cached for this row.
+    fn contains_key(tbl: &'a Tbl, key: &K) -> bool
+    where
+        K: PartialEq,
+    {
+        tbl.contains_key(&core::any::TypeId::of::<Self::Insert>(), key)
     }
 
     fn read_columns(tbl: &'a Tbl) -> Self::OuterReadGuards;
@@ -26,24 +58,201 @@ where
     fn get_row(tbl: &'a Tbl, read_columns: &'a Self::OuterReadGuards, key: &K)
         -> Self::InnerGuards;
 
+    /// Like [Row::get_row], but returns an error instead of panicking
+    /// if any column is missing the key, or the key is stale.
+    fn try_get_row(
+        tbl: &'a Tbl,
+        read_columns: &'a Self::OuterReadGuards,
+        key: &K,
+    ) -> Result<Self::InnerGuards, KeyError>;
+
+    /// Iterate over each key and its row, for use with [Aggregate](super::Aggregate).
+    ///
+    /// Borrows the table's key cache like [Row::keys], and skips keys missing from any column.
+    fn rows(tbl: &'a Tbl, read_columns: &'a Self::OuterReadGuards) -> Rows<'a, Tbl, K, Self> {
+        Rows::new(tbl, read_columns)
+    }
+
     fn write_columns(tbl: &'a Tbl) -> Self::OuterWriteGuards;
 
+    /// Borrow the values of `key` straight out of the write-locked cell maps, without locking its cells.
+    ///
+    /// Unlike [Row::get_row], this can hand out `&mut` values from two-level columns,
+    /// and only one such row can be borrowed from the guards at a time.
+    fn get_row_mut<'b>(
+        tbl: &'a Tbl,
+        write_columns: &'b mut Self::OuterWriteGuards,
+        key: &K,
+    ) -> Self::InnerRefs<'b>
+    where
+        'a: 'b;
+
+    /// Like [Row::get_row_mut], but returns an error instead of panicking
+    /// if any column is missing the key, or the key is stale.
+    fn try_get_row_mut<'b>(
+        tbl: &'a Tbl,
+        write_columns: &'b mut Self::OuterWriteGuards,
+        key: &K,
+    ) -> Result<Self::InnerRefs<'b>, KeyError>
+    where
+        'a: 'b;
+
+    /// Insert a row of values, either as [Row::Insert] or any type converting into it,
+    /// such as the named insert struct generated alongside a derived row.
+    ///
+    /// Publishes an [Event::Insert] once the cells are written.
     fn insert(
         tbl: &'a Tbl,
         write_columns: &mut Self::OuterWriteGuards,
         key: K,
-        values: Self::Insert,
+        values: impl Into<Self::Insert>,
+    ) -> Self::Result
+    where
+        K: Clone,
+    {
+        let previous = Self::insert_cells(tbl, write_columns, key.clone(), values);
+        tbl.publish(Event::Insert(key));
+        previous
+    }
+
+    /// Publishes an [Event::Insert] for each key once every cell is written.
+    fn extend<I>(
+        tbl: &'a Tbl,
+        write_columns: &mut Self::OuterWriteGuards,
+        values: impl Iterator<Item = (K, I)>,
+    ) where
+        K: Clone,
+        I: Into<Self::Insert>,
+    {
+        let mut keys = Vec::new();
+        Self::extend_cells(
+            tbl,
+            write_columns,
+            values.inspect(|(key, _)| keys.push(key.clone())),
+        );
+        for key in keys {
+            tbl.publish(Event::Insert(key));
+        }
+    }
+
+    /// Replace the cells of `key`, which must already be in every column,
+    /// and publish an [Event::Update] once they're written.
+    fn update(
+        tbl: &'a Tbl,
+        write_columns: &mut Self::OuterWriteGuards,
+        key: K,
+        values: impl Into<Self::Insert>,
+    ) -> Self::Result
+    where
+        K: Clone,
+    {
+        let previous = Self::replace_cells(tbl, write_columns, key.clone(), values);
+        tbl.publish(Event::Update(key));
+        previous
+    }
+
+    /// Remove the cells of `key`, freeing the key for recycling
+    /// once no row type in the key cache holds it.
+    fn remove(tbl: &'a Tbl, write_columns: &mut Self::OuterWriteGuards, key: &K) -> Self::Result
+    where
+        K: Clone,
+    {
+        let removed = Self::take(tbl, write_columns, key);
+        tbl.release_key(key);
+        removed
+    }
+
+    /// Like [Row::remove], but keeps the key allocated,
+    /// so that its cells can be put back with [Row::restore].
+    ///
+    /// Publishes an [Event::Remove] once the cells are gone.
+    fn take(tbl: &'a Tbl, write_columns: &mut Self::OuterWriteGuards, key: &K) -> Self::Result
+    where
+        K: Clone,
+    {
+        let removed = Self::take_cells(tbl, write_columns, key);
+        tbl.publish(Event::Remove(key.clone()));
+        removed
+    }
+
+    /// Put back the cells returned by a previous [Row::insert], [Row::update] or [Row::take],
+    /// removing any cell that did not exist beforehand.
+    ///
+    /// Publishes whichever [Event] takes the row from its current cells to the restored ones.
+    fn restore(
+        tbl: &'a Tbl,
+        write_columns: &mut Self::OuterWriteGuards,
+        key: K,
+        previous: Self::Result,
+    ) where
+        K: Clone,
+    {
+        let existed = Self::contains(write_columns, &key);
+        Self::restore_cells(tbl, write_columns, key.clone(), previous);
+        match (existed, Self::contains(write_columns, &key)) {
+            (false, true) => tbl.publish(Event::Insert(key)),
+            (true, true) => tbl.publish(Event::Update(key)),
+            (true, false) => tbl.publish(Event::Remove(key)),
+            (false, false) => (),
+        }
+    }
+
+    /// Returns true if every column in this row holds a cell for `key`.
+    fn contains(write_columns: &Self::OuterWriteGuards, key: &K) -> bool;
+
+    /// Like [Row::insert], but publishes nothing.
+    ///
+    /// Rows flattening this one write its cells through these methods,
+    /// so that each change is published once, for the outermost row.
+    fn insert_cells(
+        tbl: &'a Tbl,
+        write_columns: &mut Self::OuterWriteGuards,
+        key: K,
+        values: impl Into<Self::Insert>,
     ) -> Self::Result;
 
-    fn extend(
+    /// Like [Row::extend], but publishes nothing.
+    fn extend_cells<I>(
         tbl: &'a Tbl,
         write_columns: &mut Self::OuterWriteGuards,
-        values: impl Iterator<Item = (K, Self::Insert)>,
-    );
+        values: impl Iterator<Item = (K, I)>,
+    ) where
+        I: Into<Self::Insert>;
+
+    /// Like [Row::update], but publishes nothing.
+    ///
+    /// The key cache is left alone, so unlike [Row::insert] this doesn't add `key` to it.
+    fn replace_cells(
+        tbl: &'a Tbl,
+        write_columns: &mut Self::OuterWriteGuards,
+        key: K,
+        values: impl Into<Self::Insert>,
+    ) -> Self::Result;
+
+    /// Like [Row::take], but publishes nothing.
+    fn take_cells(
+        tbl: &'a Tbl,
+        write_columns: &mut Self::OuterWriteGuards,
+        key: &K,
+    ) -> Self::Result;
 
-    fn remove(tbl: &'a Tbl, write_columns: &mut Self::OuterWriteGuards, key: &K) -> Self::Result;
+    /// Like [Row::restore], but publishes nothing.
+    fn restore_cells(
+        tbl: &'a Tbl,
+        write_columns: &mut Self::OuterWriteGuards,
+        key: K,
+        previous: Self::Result,
+    );
 
-    fn key_cache_id(_tbl: &Tbl) -> std::any::TypeId {
-        std::any::TypeId::of::<Self::Insert>()
+    fn key_cache_id(_tbl: &Tbl) -> core::any::TypeId {
+        core::any::TypeId::of::<Self::Insert>()
     }
 }
+
+/// The named insert struct generated alongside a derived [Row],
+/// naming the [Row::Insert] tuple it converts into.
+///
+/// Lets a row `#[flatten]` another derived row without knowing its table.
+pub trait RowInsert: Into<Self::Insert> {
+    type Insert: 'static;
+}
@@ -0,0 +1,83 @@
+use alloc::{
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+
+use crate::traits::Lock;
+
+use super::Event;
+
+#[cfg(feature = "std")]
+type QueueLock<T> = std::sync::Mutex<T>;
+
+#[cfg(not(feature = "std"))]
+type QueueLock<T> = crate::locks::SpinLock<T>;
+
+type Queue<K> = QueueLock<Vec<Event<K>>>;
+
+/// The subscriptions registered on a table, as a field marked `#[subscribers]` for the `Table` derive.
+///
+/// Each [Row](super::Row) publishes an [Event] to every subscription for each key
+/// it inserts, updates or removes, once the key's cells are written, as does `despawn`.
+/// Events are raised while the row's columns are still locked,
+/// so they're queued for each subscriber to take once it's ready to read the changed cells.
+pub struct Subscribers<K> {
+    queues: QueueLock<Vec<Weak<Queue<K>>>>,
+}
+
+impl<K> Subscribers<K> {
+    /// Register a new subscription, which receives every event published from now on.
+    pub fn subscribe(&self) -> Subscription<K> {
+        let queue = Arc::new(QueueLock::default());
+        Lock::write(&self.queues).push(Arc::downgrade(&queue));
+        Subscription { queue }
+    }
+
+    /// Queue `event` for every subscription, forgetting those that have been dropped.
+    pub fn publish(&self, event: Event<K>)
+    where
+        K: Clone,
+    {
+        Lock::write(&self.queues).retain(|queue| match queue.upgrade() {
+            Some(queue) => {
+                Lock::write(&*queue).push(event.clone());
+                true
+            }
+            None => false,
+        });
+    }
+}
+
+impl<K> Default for Subscribers<K> {
+    fn default() -> Self {
+        Subscribers {
+            queues: Default::default(),
+        }
+    }
+}
+
+impl<K> core::fmt::Debug for Subscribers<K> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Subscribers")
+            .field("count", &Lock::read(&self.queues).len())
+            .finish()
+    }
+}
+
+/// The events published by a table since they were last taken, as handed out by [Publisher::subscribe].
+pub struct Subscription<K> {
+    queue: Arc<Queue<K>>,
+}
+
+impl<K> Subscription<K> {
+    /// Take every queued event, oldest first.
+    pub fn take(&self) -> Vec<Event<K>> {
+        core::mem::take(&mut *Lock::write(&*self.queue))
+    }
+}
+
+/// A table that publishes the keys its rows insert, update and remove, implemented by the `Table` derive
+/// for tables with a `#[subscribers]` field.
+pub trait Publisher<K> {
+    fn subscribe(&self) -> Subscription<K>;
+}
@@ -0,0 +1,237 @@
+use alloc::vec::Vec;
+use core::fmt::{Debug, Display};
+
+#[cfg(feature = "std")]
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+
+use super::{Keys, Row};
+
+/// A staged change to a set of [Row] columns.
+pub enum Operation<K, I> {
+    /// Insert a new set of cells, replacing any that already exist.
+    Insert(K, I),
+    /// Replace the cells of a key that must already exist in every column.
+    Update(K, I),
+    /// Remove the cells of a key that must already exist in every column.
+    Remove(K),
+}
+
+/// A change to the cells of a key, as published to a table's [Subscribers](super::Subscribers).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<K> {
+    Insert(K),
+    Update(K),
+    Remove(K),
+}
+
+impl<K> Event<K> {
+    pub fn key(&self) -> &K {
+        match self {
+            Event::Insert(key) | Event::Update(key) | Event::Remove(key) => key,
+        }
+    }
+}
+
+impl<K, I> From<&Operation<K, I>> for Event<K>
+where
+    K: Clone,
+{
+    fn from(operation: &Operation<K, I>) -> Self {
+        match operation {
+            Operation::Insert(key, _) => Event::Insert(key.clone()),
+            Operation::Update(key, _) => Event::Update(key.clone()),
+            Operation::Remove(key) => Event::Remove(key.clone()),
+        }
+    }
+}
+
+/// The reason a [Transaction] was rolled back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionError<K> {
+    /// An update or removal targeted a key that is not present in every column.
+    MissingKey(K),
+}
+
+impl<K> Display for TransactionError<K>
+where
+    K: Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            TransactionError::MissingKey(key) => write!(f, "Key {:?} is not present", key),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K> std::error::Error for TransactionError<K> where K: Debug {}
+
+/// Holds the outer write guards for a [Row]'s columns and applies a set of staged [Operation]s
+/// all together, or not at all.
+///
+/// Nothing is written until [Transaction::commit] is called.
+/// If an operation fails or panics during commit, every operation applied so far
+/// is undone in reverse order, restoring the previous inner locks and key cache entries.
+/// Recovering from panics requires the `std` feature. Cells already taken out
+/// by a column map that panics during its own `remove` can't be recovered,
+/// nor can cells an update replaced before a later column's `insert` panicked.
+///
+/// Each operation publishes its [Event] once applied, as does undoing it,
+/// so subscribers see a rolled back operation followed by its reversal.
+///
+/// Operations are hidden from other threads only as long as the column guards are,
+/// which isn't the case for self-locking maps such as [ShardedMap](crate::ShardedMap).
+pub struct Transaction<'a, Tbl, K, R>
+where
+    Tbl: Keys<'a, K>,
+    K: 'a,
+    R: Row<'a, Tbl, K>,
+    R::Insert: 'static,
+{
+    tbl: &'a Tbl,
+    columns: R::OuterWriteGuards,
+    operations: Vec<Operation<K, R::Insert>>,
+}
+
+impl<'a, Tbl, K, R> Transaction<'a, Tbl, K, R>
+where
+    Tbl: Keys<'a, K>,
+    K: Clone + 'a,
+    R: Row<'a, Tbl, K>,
+    R::Insert: 'static,
+{
+    /// Write-lock the columns of `R` and begin staging operations.
+    pub fn new(tbl: &'a Tbl) -> Self {
+        Transaction {
+            tbl,
+            columns: R::write_columns(tbl),
+            operations: Default::default(),
+        }
+    }
+
+    pub fn insert(&mut self, key: K, values: impl Into<R::Insert>) -> &mut Self {
+        self.operations.push(Operation::Insert(key, values.into()));
+        self
+    }
+
+    pub fn extend<I>(&mut self, values: impl Iterator<Item = (K, I)>) -> &mut Self
+    where
+        I: Into<R::Insert>,
+    {
+        self.operations
+            .extend(values.map(|(key, values)| Operation::Insert(key, values.into())));
+        self
+    }
+
+    pub fn update(&mut self, key: K, values: impl Into<R::Insert>) -> &mut Self {
+        self.operations.push(Operation::Update(key, values.into()));
+        self
+    }
+
+    pub fn remove(&mut self, key: K) -> &mut Self {
+        self.operations.push(Operation::Remove(key));
+        self
+    }
+
+    /// The operations staged so far, in the order they will be applied.
+    pub fn operations(&self) -> &[Operation<K, R::Insert>] {
+        &self.operations
+    }
+
+    /// Apply every staged operation, then release the column locks.
+    ///
+    /// On error, all applied operations are rolled back before returning.
+    /// On panic, all applied operations are rolled back before the panic resumes.
+    pub fn commit(self) -> Result<(), TransactionError<K>> {
+        let Transaction {
+            tbl,
+            mut columns,
+            operations,
+        } = self;
+
+        // Removed keys are only freed once the transaction can no longer restore them
+        let removed = operations
+            .iter()
+            .filter_map(|operation| match operation {
+                Operation::Remove(key) => Some(key.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut undo = Vec::with_capacity(operations.len());
+
+        #[cfg(feature = "std")]
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            Self::apply(tbl, &mut columns, operations, &mut undo)
+        }));
+
+        #[cfg(not(feature = "std"))]
+        let result: Result<_, core::convert::Infallible> =
+            Ok(Self::apply(tbl, &mut columns, operations, &mut undo));
+
+        match result {
+            Ok(Ok(())) => {
+                drop(columns);
+                for key in removed.iter() {
+                    tbl.release_key(key);
+                }
+                Ok(())
+            }
+            Ok(Err(err)) => {
+                Self::undo(tbl, &mut columns, undo);
+                Err(err)
+            }
+            #[cfg(feature = "std")]
+            Err(panic) => {
+                Self::undo(tbl, &mut columns, undo);
+                // Release the column locks before unwinding, so that they aren't poisoned
+                drop(columns);
+                resume_unwind(panic)
+            }
+        }
+    }
+
+    /// Discard every staged operation and release the column locks.
+    pub fn rollback(self) {}
+
+    /// Apply each operation, recording the cells it replaces. Inserts and removals take
+    /// the old cells before writing anything, so that an operation panicking partway through
+    /// is undone along with the rest; updates write in place, keeping the key cached.
+    fn apply(
+        tbl: &'a Tbl,
+        columns: &mut R::OuterWriteGuards,
+        operations: Vec<Operation<K, R::Insert>>,
+        undo: &mut Vec<(K, R::Result)>,
+    ) -> Result<(), TransactionError<K>> {
+        for operation in operations {
+            match operation {
+                Operation::Insert(key, values) => {
+                    let previous = R::take_cells(tbl, columns, &key);
+                    undo.push((key.clone(), previous));
+                    R::insert(tbl, columns, key, values);
+                }
+                Operation::Update(key, values) => {
+                    if !R::contains(columns, &key) {
+                        return Err(TransactionError::MissingKey(key));
+                    }
+                    let previous = R::update(tbl, columns, key.clone(), values);
+                    undo.push((key, previous));
+                }
+                Operation::Remove(key) => {
+                    if !R::contains(columns, &key) {
+                        return Err(TransactionError::MissingKey(key));
+                    }
+                    let previous = R::take(tbl, columns, &key);
+                    undo.push((key, previous));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn undo(tbl: &'a Tbl, columns: &mut R::OuterWriteGuards, undo: Vec<(K, R::Result)>) {
+        for (key, previous) in undo.into_iter().rev() {
+            R::restore(tbl, columns, key, previous);
+        }
+    }
+}
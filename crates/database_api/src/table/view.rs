@@ -0,0 +1,372 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::{
+    any::TypeId,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use crate::traits::KeyValueMap;
+
+use super::{Column, Event, Keys, Locked, Publisher, Row, Subscription};
+
+#[cfg(feature = "std")]
+type ViewLock<T> = std::sync::RwLock<T>;
+
+#[cfg(not(feature = "std"))]
+type ViewLock<T> = crate::locks::SpinLock<T>;
+
+#[cfg(feature = "std")]
+fn read<T>(lock: &ViewLock<T>) -> impl Deref<Target = T> + '_ {
+    lock.read().expect("poisoned")
+}
+
+#[cfg(not(feature = "std"))]
+fn read<T>(lock: &ViewLock<T>) -> impl Deref<Target = T> + '_ {
+    lock.read()
+}
+
+#[cfg(feature = "std")]
+fn write<T>(lock: &ViewLock<T>) -> impl DerefMut<Target = T> + '_ {
+    lock.write().expect("poisoned")
+}
+
+#[cfg(not(feature = "std"))]
+fn write<T>(lock: &ViewLock<T>) -> impl DerefMut<Target = T> + '_ {
+    lock.write()
+}
+
+/// How a [View] derives its rows from the rows of a base table `Tbl`.
+///
+/// Each base row contributes to at most one view row, which is created by its first contribution,
+/// and removed once every base row contributing to it is gone.
+pub trait ViewDefinition<Tbl> {
+    /// The key of a base row.
+    type BaseKey;
+    /// The key of a view row.
+    type Key;
+    /// The value stored for each view row.
+    type Row;
+    /// The part of a view row derived from a single base row.
+    type Contribution;
+
+    /// Read a base row, returning which view row it contributes to and with what, if any.
+    ///
+    /// Called without any of the view's locks on the base table held,
+    /// so the row can be read through any [Row] over it.
+    fn contribution(
+        &self,
+        base: &Tbl,
+        key: &Self::BaseKey,
+    ) -> Option<(Self::Key, Self::Contribution)>;
+
+    /// Start a view row from its first contribution.
+    fn create(&self, contribution: &Self::Contribution) -> Self::Row;
+
+    /// Add a further contribution to a view row.
+    fn add(&self, row: &mut Self::Row, contribution: &Self::Contribution);
+
+    /// Take a contribution back out of a view row that still has others.
+    fn retract(&self, row: &mut Self::Row, contribution: &Self::Contribution);
+}
+
+/// A difference between the stored rows of a [View] and those rebuilt from its base table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inconsistency<K, R> {
+    /// A row derived from the base table is not stored.
+    Missing { key: K, expected: R },
+    /// A stored row is not derived from the base table.
+    Unexpected { key: K, found: R },
+    /// A stored row differs from the one derived from the base table.
+    Mismatched { key: K, expected: R, found: R },
+}
+
+impl<K, R> Inconsistency<K, R> {
+    pub fn key(&self) -> &K {
+        match self {
+            Inconsistency::Missing { key, .. }
+            | Inconsistency::Unexpected { key, .. }
+            | Inconsistency::Mismatched { key, .. } => key,
+        }
+    }
+}
+
+/// The base rows currently contributing to each view row.
+struct Contributions<BK, VK, C> {
+    by_base_key: BTreeMap<BK, (VK, C)>,
+    counts: BTreeMap<VK, usize>,
+}
+
+impl<BK, VK, C> Default for Contributions<BK, VK, C> {
+    fn default() -> Self {
+        Contributions {
+            by_base_key: BTreeMap::new(),
+            counts: BTreeMap::new(),
+        }
+    }
+}
+
+type ContributionsOf<Tbl, D> = Contributions<
+    <D as ViewDefinition<Tbl>>::BaseKey,
+    <D as ViewDefinition<Tbl>>::Key,
+    <D as ViewDefinition<Tbl>>::Contribution,
+>;
+
+type ViewCells<Tbl, D> =
+    BTreeMap<<D as ViewDefinition<Tbl>>::Key, ViewLock<<D as ViewDefinition<Tbl>>::Row>>;
+
+/// A materialized view over the base rows `B` of a table, kept up to date incrementally
+/// from the [Event]s its base table publishes.
+///
+/// The view stores its rows in its own map, and acts as a table with a single column
+/// selected by the row type, so it can be read through any [Row] over that column.
+/// Its keys are derived from the base table, so rows should not be inserted into it directly.
+///
+/// The view subscribes to its base table on creation, which publishes every row inserted, updated
+/// or removed through any [Row] or [Transaction](super::Transaction) over it.
+/// Published events are applied the next time the view is read,
+/// so read it without holding the base table's write guards.
+/// Writes through existing cells aren't published;
+/// pass them to [View::notify], use [View::check] to look for drift,
+/// and [View::rebuild] to correct it.
+///
+/// The view can be read through a [Row] like any other table, but has no [NextKey](super::NextKey),
+/// as its keys are derived from the base table.
+pub struct View<'a, Tbl, B, D>
+where
+    D: ViewDefinition<Tbl>,
+{
+    base: &'a Tbl,
+    definition: D,
+    subscription: Subscription<D::BaseKey>,
+    cells: ViewLock<ViewCells<Tbl, D>>,
+    contributions: ViewLock<ContributionsOf<Tbl, D>>,
+    _phantom: PhantomData<fn() -> B>,
+}
+
+impl<'a, Tbl, BK, B, VK, D> View<'a, Tbl, B, D>
+where
+    Tbl: Keys<'a, BK> + Publisher<BK>,
+    BK: Ord + Clone + 'a,
+    B: Row<'a, Tbl, BK>,
+    B::Insert: 'static,
+    VK: Ord + Clone,
+    D: ViewDefinition<Tbl, BaseKey = BK, Key = VK>,
+{
+    /// Subscribe to the base table, and build the view from its current rows.
+    pub fn new(base: &'a Tbl, definition: D) -> Self {
+        let view = View {
+            base,
+            definition,
+            subscription: base.subscribe(),
+            cells: ViewLock::new(BTreeMap::new()),
+            contributions: ViewLock::new(Contributions::default()),
+            _phantom: PhantomData,
+        };
+        view.rebuild();
+        view
+    }
+
+    pub fn definition(&self) -> &D {
+        &self.definition
+    }
+
+    /// Recompute every view row from the base rows, replacing the stored ones.
+    pub fn rebuild(&self) {
+        let mut contributions = write(&self.contributions);
+        // Anything published so far is covered by the rebuild
+        self.subscription.take();
+        let (rebuilt, rows) = self.materialize();
+
+        *write(&self.cells) = rows
+            .into_iter()
+            .map(|(key, row)| (key, ViewLock::new(row)))
+            .collect();
+        *contributions = rebuilt;
+    }
+
+    /// Apply every event published by the base table since the view was last read.
+    pub fn refresh(&self) {
+        for event in self.subscription.take() {
+            self.notify(&event);
+        }
+    }
+
+    /// Retract the key's previous contribution, then re-read its base row.
+    ///
+    /// The row is re-read whatever the event, since a key removed by one row type
+    /// may still be held by the row type the view is over.
+    pub fn notify(&self, event: &Event<BK>) {
+        let mut contributions = write(&self.contributions);
+        let Contributions {
+            by_base_key,
+            counts,
+        } = &mut *contributions;
+        let key = event.key();
+
+        if let Some((view_key, contribution)) = by_base_key.remove(key) {
+            self.retract(counts, &view_key, &contribution);
+        }
+
+        if let Some((view_key, contribution)) = self.definition.contribution(self.base, key) {
+            self.add(counts, &view_key, &contribution);
+            by_base_key.insert(key.clone(), (view_key, contribution));
+        }
+    }
+
+    /// Rebuild the view in memory and compare it with the stored rows, leaving them untouched.
+    ///
+    /// Returns every difference found in view key order, which is empty if the view is consistent.
+    pub fn check(&self) -> Vec<Inconsistency<VK, D::Row>>
+    where
+        D::Row: Clone + PartialEq,
+    {
+        self.refresh();
+        let _contributions = read(&self.contributions);
+        let (_, mut expected) = self.materialize();
+
+        let cells = read(&self.cells);
+        let mut inconsistencies = Vec::new();
+        for (key, cell) in cells.iter() {
+            let found = read(cell).clone();
+            match expected.remove(key) {
+                Some(expected) if expected == found => (),
+                Some(expected) => inconsistencies.push(Inconsistency::Mismatched {
+                    key: key.clone(),
+                    expected,
+                    found,
+                }),
+                None => inconsistencies.push(Inconsistency::Unexpected {
+                    key: key.clone(),
+                    found,
+                }),
+            }
+        }
+
+        inconsistencies.extend(
+            expected
+                .into_iter()
+                .map(|(key, expected)| Inconsistency::Missing { key, expected }),
+        );
+        inconsistencies.sort_by(|lhs, rhs| lhs.key().cmp(rhs.key()));
+        inconsistencies
+    }
+
+    /// Derive every view row from scratch, along with the contributions it was derived from.
+    fn materialize(&self) -> (ContributionsOf<Tbl, D>, BTreeMap<VK, D::Row>) {
+        let mut contributions = Contributions::default();
+        let mut rows = BTreeMap::new();
+
+        for key in B::snapshot_keys(self.base) {
+            let (view_key, contribution) = match self.definition.contribution(self.base, &key) {
+                Some(contribution) => contribution,
+                None => continue,
+            };
+
+            match rows.get_mut(&view_key) {
+                Some(row) => self.definition.add(row, &contribution),
+                None => {
+                    rows.insert(view_key.clone(), self.definition.create(&contribution));
+                }
+            }
+            *contributions.counts.entry(view_key.clone()).or_insert(0) += 1;
+            contributions
+                .by_base_key
+                .insert(key, (view_key, contribution));
+        }
+
+        (contributions, rows)
+    }
+
+    fn add(&self, counts: &mut BTreeMap<VK, usize>, view_key: &VK, contribution: &D::Contribution) {
+        let mut cells = write(&self.cells);
+        match KeyValueMap::get(&*cells, view_key) {
+            Some(cell) => self.definition.add(&mut write(cell), contribution),
+            None => {
+                let row = self.definition.create(contribution);
+                KeyValueMap::insert(&mut *cells, view_key.clone(), ViewLock::new(row));
+            }
+        }
+        *counts.entry(view_key.clone()).or_insert(0) += 1;
+    }
+
+    fn retract(
+        &self,
+        counts: &mut BTreeMap<VK, usize>,
+        view_key: &VK,
+        contribution: &D::Contribution,
+    ) {
+        let mut cells = write(&self.cells);
+        match counts.get_mut(view_key) {
+            Some(count) if *count > 1 => {
+                *count -= 1;
+                if let Some(cell) = KeyValueMap::get(&*cells, view_key) {
+                    self.definition.retract(&mut write(cell), contribution);
+                }
+            }
+            _ => {
+                counts.remove(view_key);
+                KeyValueMap::remove(&mut *cells, view_key);
+            }
+        }
+    }
+}
+
+/// Reading the view's column applies any pending base table events first.
+impl<'a, 'v, Tbl, BK, B, VK, R, D> Column<'v, VK, R> for View<'a, Tbl, B, D>
+where
+    Tbl: Keys<'a, BK> + Publisher<BK>,
+    BK: Ord + Clone + 'a,
+    B: Row<'a, Tbl, BK>,
+    B::Insert: 'static,
+    VK: Ord + Clone + 'v,
+    R: Default + 'v,
+    D: ViewDefinition<Tbl, BaseKey = BK, Key = VK, Row = R>,
+{
+    type Value = R;
+    type OuterLock = ViewLock<BTreeMap<VK, ViewLock<R>>>;
+    type CellMap = BTreeMap<VK, ViewLock<R>>;
+    type InnerLock = ViewLock<R>;
+    type Access = Locked;
+
+    fn outer_lock(&'v self) -> &'v Self::OuterLock {
+        self.refresh();
+        &self.cells
+    }
+}
+
+impl<'a, 'v, Tbl, BK, B, VK, D> Keys<'v, VK> for View<'a, Tbl, B, D>
+where
+    Tbl: Keys<'a, BK> + Publisher<BK>,
+    BK: Ord + Clone + 'a,
+    B: Row<'a, Tbl, BK>,
+    B::Insert: 'static,
+    VK: Ord + Clone + 'v,
+    D: ViewDefinition<Tbl, BaseKey = BK, Key = VK>,
+{
+    type Keys = alloc::vec::IntoIter<VK>;
+
+    /// View keys follow its stored rows, so are not cached separately.
+    fn insert_key(&'v self, _type_id: TypeId, _key: VK) {}
+    fn extend_keys(&'v self, _type_id: TypeId, _keys: impl Iterator<Item = VK>) {}
+    fn remove_key(&'v self, _type_id: &TypeId, _key: &VK) {}
+
+    /// Copy out the keys of every stored row, whichever row type asks for them.
+    fn keys(&'v self, _type_id: &TypeId) -> Self::Keys {
+        self.refresh();
+        read(&self.cells)
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    fn key_count(&'v self, _type_id: &TypeId) -> usize {
+        self.refresh();
+        read(&self.cells).len()
+    }
+
+    fn contains_key(&'v self, _type_id: &TypeId, key: &VK) -> bool {
+        self.refresh();
+        read(&self.cells).contains_key(key)
+    }
+}
@@ -1,98 +1,365 @@
 use std::{
     any::TypeId,
-    array::IntoIter as ArrayIter,
     borrow::Cow,
     cell::RefCell,
-    collections::{BTreeMap, BTreeSet, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     ops::{Deref, DerefMut},
     sync::{atomic::AtomicUsize, Mutex, RwLock},
 };
 
-use crate as database_api;
-use crate::{FromRow, KeySet, Keys, Lock, NextKey, NextKeyIterator, Row};
+use crate::{
+    inner_report, outer_report, Aggregate, Atomic, BitSet, CellMap, Column, Event, ForeignKey,
+    FromRow, GenerationalKey, GenerationalKeys, GenerationalMap, Inconsistency, Instrumented, Join,
+    JoinKind, KeyBuilder, KeyError, KeySet, KeySetAlgebra, KeyValueMap, Keys, Lock, ManualKeys,
+    Mvcc, NextKey, NextKeyIterator, OnRemove, PrefixRange, Publisher, Query, QueryError, Reference,
+    ReferenceError, Row, ShardedMap, SparseSet, SpinLock, Subscribers, Transaction,
+    TransactionError, Value, View, ViewDefinition,
+};
+use character_table_columns::{Armor, Health};
 
 // Test Code
-// TODO: Fix Row derive when used in foreign crates
-//       Probably better to nix the CellMap-specific guards
-//       Newtype seems like the idiomatic pattern here
-//       Worst-case, move those methods into Row (could be useful for API helpers)
-
 // TODO: Fix key caching - currently only works correctly if components are inserted and queried by the same row
 //       i.e. Will break for systems that share components, need to update all interested row caches on insert
 
 // TODO: Integrate with ecs_bench_suite
-// TODO: Derive-friendly generics-driven key caching implementation
-// TODO: Can cloning the inner key cache for iteration be avoided?
 // TODO: Investigate async compatibility
 //       Looks like it would run very deep - probably better to try without it for now
 
-#[derive(Debug, Default, crate::macros::Column)]
+#[derive(Debug, Default, crate::macros::Column, crate::macros::Table)]
+#[database(reflect)]
 pub struct Table {
+    #[primary_key]
     primary_key: AtomicUsize,
 
-    #[skip_column]
+    #[key_cache]
     key_cache: RefCell<BTreeMap<TypeId, RefCell<BTreeSet<usize>>>>,
 
     ints: RefCell<BTreeMap<usize, RefCell<u32>>>,
+    #[cfg(feature = "parking_lot")]
     floats: parking_lot::RwLock<HashMap<usize, parking_lot::RwLock<f32>>>,
+    #[cfg(not(feature = "parking_lot"))]
+    floats: RwLock<HashMap<usize, RwLock<f32>>>,
     chars: RwLock<BTreeMap<usize, RwLock<char>>>,
     strs: Mutex<HashMap<usize, Mutex<Cow<'static, str>>, fnv::FnvBuildHasher>>,
+    versions: Mvcc<BTreeMap<usize, Mvcc<u64>>>,
+    flags: Instrumented<RwLock<BTreeMap<usize, Instrumented<RwLock<bool>>>>>,
+    counters: RwLock<HashMap<usize, Atomic<u16>>>,
+    readings: ShardedMap<usize, RwLock<i64>>,
+    levels: SpinLock<BTreeMap<usize, SpinLock<i8>>>,
+    positions: RwLock<SparseSet<usize, RwLock<i16>>>,
+}
+
+#[derive(Debug, Default, crate::macros::Column, crate::macros::Table)]
+pub struct SensorTable {
+    #[primary_key]
+    primary_key: AtomicUsize,
+
+    #[key_cache]
+    key_cache: RwLock<BTreeMap<TypeId, RwLock<BTreeSet<usize>>>>,
+
+    readings: ShardedMap<usize, RwLock<i64>>,
+}
+
+#[derive(Debug, Default, crate::macros::Column, crate::macros::Table)]
+pub struct EntityTable {
+    #[primary_key]
+    entities: GenerationalKeys,
+
+    #[key_cache]
+    key_cache: RwLock<HashMap<TypeId, RwLock<HashSet<GenerationalKey>>>>,
+
+    ints: RwLock<BTreeMap<GenerationalKey, RwLock<u32>>>,
+    floats: RwLock<GenerationalMap<GenerationalKey, RwLock<f32>>>,
+}
+
+type InventoryKey = (usize, u32);
+
+#[derive(Debug, Default, crate::macros::Column, crate::macros::Table)]
+pub struct InventoryTable {
+    #[primary_key]
+    keys: ManualKeys<InventoryKey>,
+
+    #[key_cache]
+    key_cache: RefCell<BTreeMap<TypeId, RefCell<BTreeSet<InventoryKey>>>>,
+
+    ints: RefCell<BTreeMap<InventoryKey, RefCell<u32>>>,
+    floats: RefCell<BTreeMap<InventoryKey, RefCell<f32>>>,
+}
+
+#[derive(Debug, Default, crate::macros::Column, crate::macros::Table)]
+pub struct LocalTable {
+    #[primary_key]
+    primary_key: AtomicUsize,
+
+    #[key_cache(usize)]
+    key_cache: RefCell<BTreeMap<TypeId, RefCell<BitSet>>>,
+
+    ints: RefCell<BTreeMap<usize, u32>>,
+    floats: RefCell<SparseSet<usize, f32>>,
+}
+
+#[derive(Debug, Default, crate::macros::Column, crate::macros::Table)]
+#[database(reflect)]
+pub struct TallyTable {
+    #[primary_key]
+    primary_key: AtomicUsize,
+
+    #[key_cache(usize)]
+    key_cache: RefCell<BTreeMap<TypeId, RefCell<BitSet>>>,
+
+    ints: RefCell<BTreeMap<usize, u32>>,
+    floats: RwLock<HashMap<usize, f32>>,
+}
+
+#[derive(Debug, Default, crate::macros::Column, crate::macros::Table)]
+pub struct CharacterTable {
+    #[primary_key]
+    primary_key: AtomicUsize,
+
+    #[key_cache]
+    key_cache: RwLock<BTreeMap<TypeId, RwLock<BTreeSet<usize>>>>,
+
+    #[subscribers]
+    subscribers: Subscribers<usize>,
+
+    health: RwLock<BTreeMap<usize, RwLock<u32>>>,
+    armor: RwLock<BTreeMap<usize, RwLock<u32>>>,
+    name: RwLock<BTreeMap<usize, RwLock<String>>>,
 }
 
-impl NextKey<usize> for Table {
-    fn next_key(&self) -> usize {
-        self.primary_key
-            .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+#[derive(Debug, Default, crate::macros::Column, crate::macros::Table)]
+pub struct ItemTable {
+    #[primary_key]
+    primary_key: AtomicUsize,
+
+    #[key_cache]
+    key_cache: RwLock<BTreeMap<TypeId, RwLock<BTreeSet<usize>>>>,
+
+    owners: RwLock<BTreeMap<usize, RwLock<ForeignKey<usize>>>>,
+    holders: RwLock<BTreeMap<usize, RwLock<Option<ForeignKey<usize>>>>>,
+    weights: RwLock<BTreeMap<usize, RwLock<f32>>>,
+}
+
+/// A map that refuses to store anything under the key 13, to exercise panic recovery.
+#[derive(Debug, Default)]
+pub struct UnluckyMap<K, V>(BTreeMap<K, V>);
+
+impl<'a, V> KeyValueMap<'a, usize, V> for UnluckyMap<usize, V>
+where
+    V: 'a,
+{
+    type Keys = std::collections::btree_map::Keys<'a, usize, V>;
+
+    fn insert(&mut self, key: usize, value: V) -> Option<V> {
+        assert_ne!(key, 13, "Unlucky key");
+        self.0.insert(key, value)
+    }
+
+    fn extend(&mut self, values: impl Iterator<Item = (usize, V)>) {
+        for (key, value) in values {
+            KeyValueMap::insert(self, key, value);
+        }
+    }
+
+    fn get(&self, key: &usize) -> Option<&V> {
+        self.0.get(key)
+    }
+
+    fn get_mut(&mut self, key: &usize) -> Option<&mut V> {
+        self.0.get_mut(key)
+    }
+
+    fn remove(&mut self, key: &usize) -> Option<V> {
+        self.0.remove(key)
+    }
+
+    fn keys(&'a self) -> Self::Keys {
+        self.0.keys()
     }
 }
 
-impl<'a> Keys<'a, usize> for Table {
-    type Keys = std::collections::btree_set::IntoIter<usize>;
+#[derive(Debug, Default, crate::macros::Column, crate::macros::Table)]
+pub struct UnluckyTable {
+    #[primary_key]
+    primary_key: AtomicUsize,
 
-    fn insert_key(&'a self, type_id: TypeId, key: usize) {
-        KeySet::insert(
-            self.key_cache
-                .write()
-                .entry(type_id)
-                .or_default()
-                .write()
-                .deref_mut(),
-            key,
-        );
+    #[key_cache]
+    key_cache: RwLock<BTreeMap<TypeId, RwLock<BTreeSet<usize>>>>,
+
+    ints: RwLock<BTreeMap<usize, RwLock<u32>>>,
+    floats: RwLock<UnluckyMap<usize, RwLock<f32>>>,
+}
+
+#[derive(Debug, crate::macros::Row)]
+pub struct WeightRow<'a> {
+    weight: &'a f32,
+}
+
+impl<'a, T> FromRow<'a, (T,)> for WeightRow<'a>
+where
+    T: Deref<Target = f32>,
+{
+    fn from_row((weight,): &'a mut (T,)) -> Self {
+        WeightRow {
+            weight: Deref::deref(weight),
+        }
+    }
+}
+
+#[derive(Debug, crate::macros::Row)]
+pub struct OwnedItemRow<'a> {
+    owner: &'a ForeignKey<usize>,
+    #[flatten]
+    item: WeightRow<'a>,
+}
+
+impl<'a, T1, T2> FromRow<'a, (T1, T2)> for OwnedItemRow<'a>
+where
+    T1: Deref<Target = ForeignKey<usize>>,
+    WeightRow<'a>: FromRow<'a, T2>,
+{
+    fn from_row((owner, item): &'a mut (T1, T2)) -> Self {
+        OwnedItemRow {
+            owner: Deref::deref(owner),
+            item: WeightRow::from_row(item),
+        }
     }
+}
 
-    fn extend_keys(&'a self, type_id: TypeId, keys: impl Iterator<Item = usize>) {
-        KeySet::extend(
-            self.key_cache
-                .write()
-                .entry(type_id)
-                .or_default()
-                .write()
-                .deref_mut(),
-            keys,
-        );
+#[derive(Debug, crate::macros::Row)]
+pub struct HeldItemRow<'a> {
+    holder: &'a Option<ForeignKey<usize>>,
+    #[flatten]
+    item: WeightRow<'a>,
+}
+
+impl<'a, T1, T2> FromRow<'a, (T1, T2)> for HeldItemRow<'a>
+where
+    T1: Deref<Target = Option<ForeignKey<usize>>>,
+    WeightRow<'a>: FromRow<'a, T2>,
+{
+    fn from_row((holder, item): &'a mut (T1, T2)) -> Self {
+        HeldItemRow {
+            holder: Deref::deref(holder),
+            item: WeightRow::from_row(item),
+        }
     }
+}
 
-    fn remove_key(&'a self, type_id: &TypeId, key: &usize) {
-        KeySet::remove(
-            self.key_cache
-                .write()
-                .get(type_id)
-                .expect("Tried to uncache a non-cached key.")
-                .write()
-                .deref_mut(),
-            key,
-        );
+#[derive(Debug, crate::macros::Row)]
+pub struct CharacterRow<'a> {
+    #[column(health)]
+    health: &'a mut u32,
+    #[column(character_table_columns::Armor)]
+    #[default]
+    armor: &'a u32,
+    name: &'a String,
+}
+
+impl<'a, T1, T2, T3> FromRow<'a, (T1, T2, T3)> for CharacterRow<'a>
+where
+    T1: DerefMut<Target = u32>,
+    T2: Deref<Target = u32>,
+    T3: Deref<Target = String>,
+{
+    fn from_row((health, armor, name): &'a mut (T1, T2, T3)) -> Self {
+        CharacterRow {
+            health: DerefMut::deref_mut(health),
+            armor: Deref::deref(armor),
+            name: Deref::deref(name),
+        }
     }
+}
 
-    fn keys(&'a self, type_id: &TypeId) -> Self::Keys {
-        self.key_cache
-            .read()
-            .get(type_id)
-            .unwrap()
-            .read()
-            .clone()
-            .into_iter()
+#[derive(Debug, crate::macros::Row)]
+pub struct StatsRow<'a> {
+    #[column(health)]
+    health: &'a mut u32,
+    #[column(armor)]
+    armor: &'a u32,
+}
+
+impl<'a, T1, T2> FromRow<'a, (T1, T2)> for StatsRow<'a>
+where
+    T1: DerefMut<Target = u32>,
+    T2: Deref<Target = u32>,
+{
+    fn from_row((health, armor): &'a mut (T1, T2)) -> Self {
+        StatsRow {
+            health: DerefMut::deref_mut(health),
+            armor: Deref::deref(armor),
+        }
+    }
+}
+
+#[derive(Debug, crate::macros::Row)]
+pub struct NamedStatsRow<'a> {
+    name: &'a String,
+    #[flatten]
+    stats: StatsRow<'a>,
+}
+
+impl<'a, T1, T2> FromRow<'a, (T1, T2)> for NamedStatsRow<'a>
+where
+    T1: Deref<Target = String>,
+    StatsRow<'a>: FromRow<'a, T2>,
+{
+    fn from_row((name, stats): &'a mut (T1, T2)) -> Self {
+        NamedStatsRow {
+            name: Deref::deref(name),
+            stats: StatsRow::from_row(stats),
+        }
+    }
+}
+
+// Only used to write names
+#[allow(dead_code)]
+#[derive(Debug, crate::macros::Row)]
+pub struct NameRow<'a> {
+    name: &'a String,
+}
+
+#[derive(Debug, crate::macros::Row)]
+pub struct TotalRow<'a> {
+    total: &'a u32,
+}
+
+impl<'a, T> FromRow<'a, (T,)> for TotalRow<'a>
+where
+    T: Deref<Target = u32>,
+{
+    fn from_row((total,): &'a mut (T,)) -> Self {
+        TotalRow {
+            total: Deref::deref(total),
+        }
+    }
+}
+
+/// Total health per armor class.
+pub struct HealthByArmor;
+
+impl ViewDefinition<CharacterTable> for HealthByArmor {
+    type BaseKey = usize;
+    type Key = u32;
+    type Row = u32;
+    type Contribution = u32;
+
+    fn contribution(&self, base: &CharacterTable, key: &usize) -> Option<(u32, u32)> {
+        let columns = StatsRow::read_columns(base);
+        let (health, armor) = StatsRow::try_get_row(base, &columns, key).ok()?;
+        Some((*armor, *health))
+    }
+
+    fn create(&self, health: &u32) -> u32 {
+        *health
+    }
+
+    fn add(&self, total: &mut u32, health: &u32) {
+        *total += health;
+    }
+
+    fn retract(&self, total: &mut u32, health: &u32) {
+        *total -= health;
     }
 }
 
@@ -115,6 +382,23 @@ where
     }
 }
 
+#[derive(Debug, Clone, PartialEq, crate::macros::Row)]
+pub struct IntFloatSnapshot {
+    int: u32,
+    float: f32,
+}
+
+impl<'a> FromRow<'a, (u32, f32)> for IntFloatSnapshot {
+    fn from_row((int, float): &'a mut (u32, f32)) -> Self {
+        IntFloatSnapshot {
+            int: *int,
+            float: *float,
+        }
+    }
+}
+
+// Only printed through Debug
+#[allow(dead_code)]
 #[derive(Debug, crate::macros::Row)]
 pub struct CharStrRow<'a> {
     char: &'a char,
@@ -134,6 +418,54 @@ where
     }
 }
 
+#[derive(Debug, crate::macros::Row)]
+pub struct VersionRow<'a> {
+    version: &'a mut u64,
+}
+
+impl<'a, T1> FromRow<'a, (T1,)> for VersionRow<'a>
+where
+    T1: DerefMut<Target = u64>,
+{
+    fn from_row((version,): &'a mut (T1,)) -> Self {
+        VersionRow {
+            version: DerefMut::deref_mut(version),
+        }
+    }
+}
+
+#[derive(Debug, crate::macros::Row)]
+pub struct FlagRow<'a> {
+    flag: &'a mut bool,
+}
+
+impl<'a, T1> FromRow<'a, (T1,)> for FlagRow<'a>
+where
+    T1: DerefMut<Target = bool>,
+{
+    fn from_row((flag,): &'a mut (T1,)) -> Self {
+        FlagRow {
+            flag: DerefMut::deref_mut(flag),
+        }
+    }
+}
+
+#[derive(Debug, crate::macros::Row)]
+pub struct ReadingRow<'a> {
+    reading: &'a mut i64,
+}
+
+impl<'a, T1> FromRow<'a, (T1,)> for ReadingRow<'a>
+where
+    T1: DerefMut<Target = i64>,
+{
+    fn from_row((reading,): &'a mut (T1,)) -> Self {
+        ReadingRow {
+            reading: DerefMut::deref_mut(reading),
+        }
+    }
+}
+
 #[test]
 fn test_database_api() {
     // Create table
@@ -146,7 +478,7 @@ fn test_database_api() {
     IntFloatRow::extend(
         &table,
         &mut columns,
-        NextKeyIterator::new(&table).zip(ArrayIter::new([
+        NextKeyIterator::new(&table).zip(IntoIterator::into_iter([
             (10, 10.0),
             (20, 20.0),
             (30, 30.0),
@@ -170,7 +502,7 @@ fn test_database_api() {
     CharStrRow::extend(
         &table,
         &mut columns,
-        NextKeyIterator::new(&table).zip(ArrayIter::new([
+        NextKeyIterator::new(&table).zip(IntoIterator::into_iter([
             ('a', "Foo".into()),
             ('b', "Bar".into()),
             ('c', "Baz".into()),
@@ -197,3 +529,1418 @@ fn test_database_api() {
         println!("Key {}: {:#?}", key, char_str_row);
     }
 }
+
+#[test]
+fn test_transaction() {
+    let table = Table::default();
+
+    // Committed operations are all applied
+    let mut transaction = Transaction::<_, _, IntFloatRow>::new(&table);
+    transaction
+        .insert(0, (10, 10.0))
+        .insert(1, (20, 20.0))
+        .insert(2, (30, 30.0))
+        .update(1, (25, 25.0))
+        .remove(2);
+    transaction.commit().unwrap();
+
+    assert_eq!(IntFloatRow::keys(&table).collect::<Vec<_>>(), vec![0, 1]);
+
+    // A failing operation rolls back everything before it
+    let mut transaction = Transaction::<_, _, IntFloatRow>::new(&table);
+    transaction
+        .insert(3, (40, 40.0))
+        .update(0, (15, 15.0))
+        .remove(1)
+        .update(2, (35, 35.0));
+    assert_eq!(transaction.commit(), Err(TransactionError::MissingKey(2)));
+
+    assert_eq!(IntFloatRow::keys(&table).collect::<Vec<_>>(), vec![0, 1]);
+
+    let columns = IntFloatRow::read_columns(&table);
+    for (key, expected) in IntoIterator::into_iter([(0, 10), (1, 25)]) {
+        let mut row = IntFloatRow::get_row(&table, &columns, &key);
+        let int_float_row = IntFloatRow::from_row(&mut row);
+        assert_eq!(*int_float_row.int, expected);
+    }
+    drop(columns);
+
+    // Rolling back discards staged operations
+    let mut transaction = Transaction::<_, _, IntFloatRow>::new(&table);
+    transaction.remove(0);
+    transaction.rollback();
+
+    assert_eq!(IntFloatRow::keys(&table).collect::<Vec<_>>(), vec![0, 1]);
+
+    // Updates are written in place, and published as such
+    let table = CharacterTable::default();
+    let mut transaction = Transaction::<_, _, StatsRow>::new(&table);
+    transaction.insert(0, (100, 5));
+    transaction.commit().unwrap();
+
+    let subscription = table.subscribe();
+    let mut transaction = Transaction::<_, _, StatsRow>::new(&table);
+    transaction.update(0, (80, 5));
+    transaction.commit().unwrap();
+
+    assert_eq!(subscription.take(), vec![Event::Update(0)]);
+    assert_eq!(StatsRow::keys(&table).collect::<Vec<_>>(), vec![0]);
+}
+
+#[test]
+fn test_transaction_panic() {
+    let table = UnluckyTable::default();
+
+    let mut transaction = Transaction::<_, _, IntFloatRow>::new(&table);
+    transaction.insert(0, (10, 10.0));
+    transaction.commit().unwrap();
+
+    // A panic partway through an insert rolls back everything, including the panicking insert
+    let panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let mut transaction = Transaction::<_, _, IntFloatRow>::new(&table);
+        transaction
+            .insert(1, (20, 20.0))
+            .update(0, (15, 15.0))
+            .insert(13, (130, 130.0));
+        transaction.commit()
+    }));
+    assert!(panic.is_err());
+
+    // The column locks weren't poisoned, so the table is still usable
+    assert_eq!(IntFloatRow::snapshot_keys(&table), vec![0]);
+
+    {
+        let columns = IntFloatRow::read_columns(&table);
+        assert_eq!(columns.0.len(), 1);
+        let mut row = IntFloatRow::get_row(&table, &columns, &0);
+        let int_float_row = IntFloatRow::from_row(&mut row);
+        assert_eq!((*int_float_row.int, *int_float_row.float), (10, 10.0));
+    }
+
+    let mut transaction = Transaction::<_, _, IntFloatRow>::new(&table);
+    transaction.insert(1, (20, 20.0));
+    transaction.commit().unwrap();
+    assert_eq!(IntFloatRow::snapshot_keys(&table), vec![0, 1]);
+}
+
+#[test]
+fn test_mvcc() {
+    let table = Table::default();
+
+    let mut columns = VersionRow::write_columns(&table);
+    VersionRow::extend(
+        &table,
+        &mut columns,
+        NextKeyIterator::new(&table).zip(IntoIterator::into_iter([(1,), (2,)])),
+    );
+    drop(columns);
+
+    // Readers take a snapshot of the published version
+    let snapshot = VersionRow::read_columns(&table);
+    assert_eq!(snapshot.0.version(), 1);
+
+    // Writers don't wait for readers, and publish a new version on drop
+    let mut columns = VersionRow::write_columns(&table);
+    VersionRow::insert(&table, &mut columns, 2, (3,));
+    drop(columns);
+
+    assert_eq!(Column::<usize, u64>::read_cell_map(&table).version(), 2);
+
+    // The snapshot is unaffected
+    assert_eq!(snapshot.0.keys().copied().collect::<Vec<_>>(), vec![0, 1]);
+    let mut row = VersionRow::get_row(&table, &snapshot, &1);
+    assert_eq!(*VersionRow::from_row(&mut row).version, 2);
+    drop(row);
+    drop(snapshot);
+
+    // New readers see the latest version
+    let columns = VersionRow::read_columns(&table);
+    assert_eq!(columns.0.keys().copied().collect::<Vec<_>>(), vec![0, 1, 2]);
+    for key in VersionRow::keys(&table) {
+        let mut row = VersionRow::get_row(&table, &columns, &key);
+        let version_row = VersionRow::from_row(&mut row);
+        *version_row.version += 1;
+        assert_eq!(*version_row.version, key as u64 + 2);
+    }
+    drop(columns);
+
+    // Cells written through a writer's copy are published with it, leaving older snapshots as they were
+    let snapshot = Column::<usize, u64>::read_cell_map(&table);
+    let writer = Column::<usize, u64>::write_cell_map(&table);
+    *CellMap::write_cell(&*writer, &0).unwrap() = 100;
+    drop(writer);
+
+    assert_eq!(*CellMap::read_cell(&*snapshot, &0).unwrap(), 2);
+    drop(snapshot);
+    let snapshot = Column::<usize, u64>::read_cell_map(&table);
+    assert_eq!(*CellMap::read_cell(&*snapshot, &0).unwrap(), 100);
+    let version = snapshot.version();
+    drop(snapshot);
+
+    // A writer that panics partway through publishes nothing
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let mut writer = Column::<usize, u64>::write_cell_map(&table);
+        CellMap::insert(&mut *writer, 10, 10);
+        panic!("Write interrupted");
+    }));
+    assert!(result.is_err());
+
+    let snapshot = Column::<usize, u64>::read_cell_map(&table);
+    assert_eq!(snapshot.version(), version);
+    assert!(CellMap::read_cell(&*snapshot, &10).is_none());
+    drop(snapshot);
+
+    let mut writer = Column::<usize, u64>::write_cell_map(&table);
+    CellMap::insert(&mut *writer, 10, 10);
+    drop(writer);
+    assert_eq!(
+        Column::<usize, u64>::read_cell_map(&table).version(),
+        version + 1
+    );
+}
+
+#[test]
+fn test_instrumented() {
+    let table = Table::default();
+
+    let mut columns = FlagRow::write_columns(&table);
+    FlagRow::extend(
+        &table,
+        &mut columns,
+        NextKeyIterator::new(&table).zip(IntoIterator::into_iter([(false,), (true,), (false,)])),
+    );
+    drop(columns);
+
+    let columns = FlagRow::read_columns(&table);
+    let columns_again = FlagRow::read_columns(&table);
+    for key in FlagRow::keys(&table) {
+        let mut row = FlagRow::get_row(&table, &columns, &key);
+        let flag_row = FlagRow::from_row(&mut row);
+        *flag_row.flag = !*flag_row.flag;
+    }
+    drop(columns_again);
+    drop(columns);
+
+    let outer = outer_report::<_, usize, bool>(&table);
+    assert_eq!(outer.reads, 2);
+    assert_eq!(outer.writes, 1);
+    assert_eq!(outer.peak_readers, 2);
+
+    let inner = inner_report(&*Column::<usize, bool>::read_cell_map(&table));
+    assert_eq!(inner.reads, 0);
+    assert_eq!(inner.writes, 3);
+    println!("Outer: {}\nInner: {}", outer, inner);
+}
+
+#[test]
+fn test_atomic() {
+    let table = Table::default();
+
+    let mut counters = Column::<usize, u16>::write_cell_map(&table);
+    CellMap::extend(&mut *counters, (0..4).map(|key| (key, 0)));
+    drop(counters);
+
+    // Counters can be incremented through a shared cell map from many threads at once
+    let counters = Column::<usize, u16>::read_cell_map(&table);
+    let counters = &*counters;
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(move || {
+                for i in 0..1000 {
+                    *CellMap::write_cell(counters, &(i % 4)).unwrap() += 1;
+                }
+            });
+        }
+    });
+
+    for key in 0..4 {
+        assert_eq!(*CellMap::read_cell(counters, &key).unwrap(), 1000);
+    }
+}
+
+#[test]
+fn test_sharded_map() {
+    let table = SensorTable::default();
+
+    // Writers on different threads each hold their own column guards at once
+    let barrier = std::sync::Barrier::new(4);
+    std::thread::scope(|scope| {
+        for thread in 0..4 {
+            let (table, barrier) = (&table, &barrier);
+            scope.spawn(move || {
+                let mut columns = ReadingRow::write_columns(table);
+                barrier.wait();
+                ReadingRow::extend(
+                    table,
+                    &mut columns,
+                    (0..250)
+                        .map(|i| thread * 250 + i)
+                        .map(|key| (key, (key as i64,))),
+                );
+            });
+        }
+    });
+    assert_eq!(ReadingRow::keys(&table).count(), 1000);
+
+    let readings = Column::<usize, i64>::read_cell_map(&table);
+    assert_eq!(readings.len(), 1000);
+    assert_eq!(KeyValueMap::keys(&*readings).count(), 1000);
+    for key in 0..1000 {
+        assert_eq!(*CellMap::read_cell(&*readings, &key).unwrap(), key as i64);
+    }
+    drop(readings);
+
+    // Rows look cells up with every shard read-locked, then write through the cell locks
+    let columns = ReadingRow::read_columns(&table);
+    let mut row = ReadingRow::get_row(&table, &columns, &7);
+    *ReadingRow::from_row(&mut row).reading += 100;
+    drop(row);
+    drop(columns);
+    assert_eq!(
+        *CellMap::read_cell(&*Column::<usize, i64>::read_cell_map(&table), &7).unwrap(),
+        107
+    );
+
+    // Single-threaded access goes through KeyValueMap as usual
+    let mut readings = Column::<usize, i64>::write_cell_map(&table);
+    assert!(CellMap::remove(&mut *readings, &500).is_some());
+    assert!(CellMap::read_cell(&*readings, &500).is_none());
+    assert_eq!(readings.len(), 999);
+    drop(readings);
+
+    // Write guards release each shard after writing it, so transactions aren't atomic:
+    // another thread sees the first operation applied while the second is still pending
+    let applied = std::sync::Barrier::new(2);
+    let checked = std::sync::Barrier::new(2);
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            let mut columns = ReadingRow::write_columns(&table);
+            ReadingRow::insert(&table, &mut columns, 1000, (1000,));
+            applied.wait();
+            checked.wait();
+            ReadingRow::insert(&table, &mut columns, 1001, (1001,));
+        });
+
+        applied.wait();
+        let readings = Column::<usize, i64>::read_cell_map(&table);
+        assert!(CellMap::read_cell(&*readings, &1000).is_some());
+        assert!(CellMap::read_cell(&*readings, &1001).is_none());
+        drop(readings);
+        checked.wait();
+    });
+    assert!(CellMap::read_cell(&*Column::<usize, i64>::read_cell_map(&table), &1001).is_some());
+}
+
+#[test]
+fn test_spin_lock() {
+    let table = Table::default();
+
+    let mut levels = Column::<usize, i8>::write_cell_map(&table);
+    CellMap::extend(&mut *levels, (0..4).map(|key| (key, 0)));
+    drop(levels);
+
+    // Readers and writers of both lock levels can run on many threads at once
+    let levels = Column::<usize, i8>::outer_lock(&table);
+    std::thread::scope(|scope| {
+        for thread in 0..4 {
+            scope.spawn(move || {
+                for i in 0..100 {
+                    let cells = Lock::read(levels);
+                    *CellMap::write_cell(&*cells, &(i % 4)).unwrap() += 1;
+                }
+                CellMap::insert(&mut *Lock::write(levels), thread + 4, 1);
+            });
+        }
+    });
+
+    let levels = Column::<usize, i8>::read_cell_map(&table);
+    for key in 0..8 {
+        let expected = if key < 4 { 100 } else { 1 };
+        assert_eq!(*CellMap::read_cell(&*levels, &key).unwrap(), expected);
+    }
+}
+
+#[test]
+fn test_generational_keys() {
+    let keys = GenerationalKeys::default();
+    let mut map = GenerationalMap::<GenerationalKey, RefCell<u32>>::default();
+
+    let first = keys.next_key();
+    let second = keys.next_key();
+    CellMap::insert(&mut map, first, 1);
+    CellMap::insert(&mut map, second, 2);
+
+    // Freeing a key recycles its index under a new generation
+    CellMap::remove(&mut map, &first);
+    keys.free_key(&first);
+    assert!(keys.is_stale(&first));
+
+    let third = keys.next_key();
+    assert_eq!(third.index(), first.index());
+    assert!(third.generation() > first.generation());
+    CellMap::insert(&mut map, third, 3);
+
+    // Old handles are reported as stale instead of reading the new entity
+    assert_eq!(
+        CellMap::try_read_cell(&map, &first).err(),
+        Some(KeyError::Stale)
+    );
+    assert_eq!(*CellMap::try_read_cell(&map, &third).unwrap(), 3);
+    assert_eq!(*CellMap::try_read_cell(&map, &second).unwrap(), 2);
+    assert_eq!(keys.len(), 2);
+
+    // Inserting under a stale key leaves the newer entity alone
+    assert_eq!(
+        CellMap::try_insert(&mut map, first, 4).err(),
+        Some(KeyError::Stale)
+    );
+    assert!(CellMap::insert(&mut map, first, 4).is_none());
+    assert_eq!(*CellMap::try_read_cell(&map, &third).unwrap(), 3);
+
+    // Generations compare across wraparound
+    let mut map = GenerationalMap::<GenerationalKey, RefCell<u32>>::default();
+    let (old, new) = (
+        GenerationalKey::new(0, u32::MAX),
+        GenerationalKey::new(0, 0),
+    );
+    CellMap::insert(&mut map, new, 1);
+    assert_eq!(
+        CellMap::try_read_cell(&map, &old).err(),
+        Some(KeyError::Stale)
+    );
+    assert_eq!(
+        CellMap::try_insert(&mut map, old, 2).err(),
+        Some(KeyError::Stale)
+    );
+
+    // Rows report missing keys instead of panicking
+    let table = Table::default();
+    let columns = IntFloatRow::read_columns(&table);
+    assert_eq!(
+        IntFloatRow::try_get_row(&table, &columns, &0).err(),
+        Some(KeyError::Missing)
+    );
+}
+
+#[test]
+fn test_table_derive() {
+    let table = EntityTable::default();
+
+    let mut columns = IntFloatRow::write_columns(&table);
+    IntFloatRow::extend(
+        &table,
+        &mut columns,
+        NextKeyIterator::new(&table).zip(IntoIterator::into_iter([(1, 1.0), (2, 2.0)])),
+    );
+    drop(columns);
+
+    let mut keys = IntFloatRow::keys(&table).collect::<Vec<_>>();
+    keys.sort();
+    assert_eq!(keys.len(), 2);
+
+    // Removing the only row holding the first key frees it, recycling its index
+    let mut columns = IntFloatRow::write_columns(&table);
+    IntFloatRow::remove(&table, &mut columns, &keys[0]);
+    assert!(table.is_stale(&keys[0]));
+
+    let recycled = table.next_key();
+    assert_eq!(recycled.index(), keys[0].index());
+    IntFloatRow::insert(&table, &mut columns, recycled, (3, 3.0));
+    drop(columns);
+
+    // The stale handle no longer resolves to a row
+    let columns = IntFloatRow::read_columns(&table);
+    assert_eq!(
+        IntFloatRow::try_get_row(&table, &columns, &keys[0]).err(),
+        Some(KeyError::Stale)
+    );
+
+    let mut row = IntFloatRow::try_get_row(&table, &columns, &recycled).unwrap();
+    assert_eq!(*IntFloatRow::from_row(&mut row).int, 3);
+    drop(row);
+
+    // Keys whose index was never handed out are missing rather than stale
+    let key = GenerationalKey::new(100, 0);
+    assert!(!table.is_stale(&key));
+    assert_eq!(
+        IntFloatRow::try_get_row(&table, &columns, &key).err(),
+        Some(KeyError::Missing)
+    );
+
+    // Generations wrap around, so only the current generation of an index is live
+    let key = GenerationalKey::new(recycled.index(), recycled.generation().wrapping_sub(2));
+    assert!(table.is_stale(&key));
+}
+
+#[test]
+fn test_composite_keys() {
+    let table = InventoryTable::default();
+
+    let mut columns = IntFloatRow::write_columns(&table);
+    IntFloatRow::extend(
+        &table,
+        &mut columns,
+        IntoIterator::into_iter([(2, 10), (1, 20), (2, 30), (3, 40)]).map(|(user, item)| {
+            (
+                KeyBuilder::new(user).then(item).build(),
+                (item, item as f32 / 10.0),
+            )
+        }),
+    );
+    drop(columns);
+
+    let mut keys = IntFloatRow::keys(&table).collect::<Vec<_>>();
+    keys.sort();
+    assert_eq!(keys, vec![(1, 20), (2, 10), (2, 30), (3, 40)]);
+
+    // Prefix queries only visit the items of the given user
+    let ints = Column::<_, u32>::read_cell_map(&table);
+    let items = ints
+        .prefix_range(2)
+        .map(|(key, int)| (key.1, *int.borrow()))
+        .collect::<Vec<_>>();
+    assert_eq!(items, vec![(10, 10), (30, 30)]);
+    assert_eq!(ints.prefix_range(4).count(), 0);
+    drop(ints);
+
+    let columns = IntFloatRow::read_columns(&table);
+    let mut row = IntFloatRow::get_row(&table, &columns, &(3, 40));
+    let row = IntFloatRow::from_row(&mut row);
+    assert_eq!(*row.int, 40);
+    assert_eq!(*row.float, 4.0);
+}
+
+#[test]
+fn test_sparse_set() {
+    let table = Table::default();
+
+    let mut positions = Column::<usize, i16>::write_cell_map(&table);
+    CellMap::extend(&mut *positions, (0..8).map(|key| (key, key as i16 * 10)));
+    assert_eq!(positions.len(), 8);
+
+    // Removing swaps the last entry into the hole
+    assert_eq!(
+        *CellMap::remove(&mut *positions, &2)
+            .unwrap()
+            .read()
+            .unwrap(),
+        20
+    );
+    assert!(CellMap::remove(&mut *positions, &2).is_none());
+    assert_eq!(
+        KeyValueMap::keys(&*positions).copied().collect::<Vec<_>>(),
+        vec![0, 1, 7, 3, 4, 5, 6]
+    );
+    drop(positions);
+
+    let positions = Column::<usize, i16>::read_cell_map(&table);
+    for key in [0, 1, 3, 4, 5, 6, 7] {
+        assert_eq!(
+            *CellMap::read_cell(&*positions, &key).unwrap(),
+            key as i16 * 10
+        );
+    }
+    assert!(CellMap::read_cell(&*positions, &2).is_none());
+    assert!(CellMap::read_cell(&*positions, &100).is_none());
+
+    // Dense iteration visits only the occupied values
+    let sum = positions
+        .values()
+        .iter()
+        .map(|value| *value.read().unwrap())
+        .sum::<i16>();
+    assert_eq!(sum, 260);
+}
+
+#[test]
+fn test_bit_set() {
+    let evens = (0..200).step_by(2).collect::<BitSet>();
+    let threes = (0..200).step_by(3).collect::<BTreeSet<usize>>();
+
+    // Set algebra works across set types
+    let both = evens.intersection(&threes).copied().collect::<Vec<_>>();
+    assert_eq!(both, (0..200).step_by(6).collect::<Vec<_>>());
+    assert_eq!(evens.union(&threes).count(), 100 + 67 - 34);
+    assert_eq!(evens.difference(&threes).count(), 100 - 34);
+
+    let mut in_place = evens.clone();
+    in_place.intersect_with(&threes);
+    assert_eq!(in_place.iter().copied().collect::<Vec<_>>(), both);
+
+    let mut in_place = threes.clone();
+    in_place.difference_with(&evens);
+    assert_eq!(in_place.len(), 67 - 34);
+
+    // Keys of several columns combine a word at a time
+    let table = Table::default();
+    let mut ints = Column::<usize, u32>::write_cell_map(&table);
+    CellMap::extend(&mut *ints, (0..100).map(|key| (key, key as u32)));
+    let mut chars = Column::<usize, char>::write_cell_map(&table);
+    CellMap::extend(&mut *chars, (50..150).map(|key| (key, 'a')));
+    let mut levels = Column::<usize, i8>::write_cell_map(&table);
+    CellMap::extend(&mut *levels, (0..200).step_by(10).map(|key| (key, 0)));
+
+    let mut keys = KeyValueMap::keys(&*ints).copied().collect::<BitSet>();
+    keys.intersect_with(&KeyValueMap::keys(&*chars).copied().collect::<BitSet>());
+    keys.difference_with(&KeyValueMap::keys(&*levels).copied().collect::<BitSet>());
+    assert_eq!(keys.len(), 45);
+    assert!(!keys.contains(&60));
+    assert!(keys.contains(&61));
+
+    keys.union_with(&(0..10).collect::<BitSet>());
+    assert_eq!(keys.iter().next(), Some(&0));
+    assert_eq!(keys.len(), 55);
+
+    keys.difference_with(&keys.clone());
+    assert!(keys.is_empty());
+    assert_eq!(keys, BitSet::new());
+}
+
+#[test]
+fn test_snapshot_keys() {
+    let table = Table::default();
+
+    let mut columns = IntFloatRow::write_columns(&table);
+    IntFloatRow::extend(
+        &table,
+        &mut columns,
+        NextKeyIterator::new(&table).zip((0..10).map(|i| (i, i as f32))),
+    );
+    drop(columns);
+
+    // Borrowed iteration reads straight from the key cache
+    let keys = IntFloatRow::keys(&table);
+    assert!(table.key_cache.try_borrow_mut().is_err());
+    assert_eq!(keys.sum::<usize>(), 45);
+    assert!(table.key_cache.try_borrow_mut().is_ok());
+
+    // Snapshots release the cache, so rows can be removed while visiting keys
+    let mut columns = IntFloatRow::write_columns(&table);
+    for key in IntFloatRow::snapshot_keys(&table) {
+        if key % 2 == 0 {
+            IntFloatRow::remove(&table, &mut columns, &key);
+        }
+    }
+    drop(columns);
+
+    assert_eq!(
+        IntFloatRow::keys(&table).collect::<Vec<_>>(),
+        vec![1, 3, 5, 7, 9]
+    );
+}
+
+/// Exercises the guards owned by [crate::CachedKeys]; run under Miri with
+/// `cargo +nightly miri test -p database_api test_cached_keys`.
+#[test]
+fn test_cached_keys() {
+    let table = CharacterTable::default();
+
+    let mut columns = CharacterRow::write_columns(&table);
+    CharacterRow::extend(
+        &table,
+        &mut columns,
+        NextKeyIterator::new(&table).zip((0..4).map(|i| (i, i, i.to_string()))),
+    );
+    drop(columns);
+
+    // Iterators can move while borrowing through their guards
+    let mut keys = CharacterRow::keys(&table);
+    assert_eq!(keys.next(), Some(0));
+    let mut keys = Box::new(keys);
+    assert_eq!(keys.next(), Some(1));
+    let keys = *keys;
+    assert!(table.key_cache.try_write().is_err());
+
+    // Dropping an iterator partway through releases both guards
+    drop(keys);
+    assert!(table.key_cache.try_write().is_ok());
+    let cache = table.key_cache.read().unwrap();
+    let set = cache.get(&CharacterRow::key_cache_id(&table)).unwrap();
+    assert!(set.try_write().is_ok());
+    drop(cache);
+
+    // Uncached row types only hold the outer guard
+    let mut keys = Keys::keys(&table, &TypeId::of::<()>());
+    assert!(table.key_cache.try_write().is_err());
+    assert_eq!(keys.next(), None);
+    drop(keys);
+    assert!(table.key_cache.try_write().is_ok());
+
+    // Several iterators can read the cache at once
+    let pairs = CharacterRow::keys(&table)
+        .flat_map(|a| CharacterRow::keys(&table).map(move |b| (a, b)))
+        .count();
+    assert_eq!(pairs, 16);
+}
+
+#[test]
+fn test_column_markers() {
+    let table = CharacterTable::default();
+
+    let mut columns = CharacterRow::write_columns(&table);
+    CharacterRow::extend(
+        &table,
+        &mut columns,
+        NextKeyIterator::new(&table).zip(IntoIterator::into_iter([
+            (100, 5, "knight".to_string()),
+            (60, 1, "rogue".to_string()),
+        ])),
+    );
+    drop(columns);
+
+    // Columns sharing a value type are kept apart by their markers
+    let columns = CharacterRow::read_columns(&table);
+    let mut guards = CharacterRow::get_row(&table, &columns, &0);
+    let row = CharacterRow::from_row(&mut guards);
+    *row.health -= *row.armor;
+    assert_eq!(*row.name, "knight");
+    drop(guards);
+    drop(columns);
+
+    let health = Column::<usize, Health>::read_cell_map(&table);
+    let armor = Column::<usize, Armor>::read_cell_map(&table);
+    assert_eq!(*CellMap::read_cell(&*health, &0).unwrap(), 95);
+    assert_eq!(*CellMap::read_cell(&*armor, &1).unwrap(), 1);
+    drop((health, armor));
+
+    // Unambiguous value types select their column directly
+    let names = Column::<usize, String>::read_cell_map(&table);
+    assert_eq!(*CellMap::read_cell(&*names, &1).unwrap(), "rogue");
+}
+
+#[test]
+fn test_owned_row() {
+    let table = Table::default();
+
+    let mut columns = IntFloatRow::write_columns(&table);
+    IntFloatRow::extend(
+        &table,
+        &mut columns,
+        NextKeyIterator::new(&table).zip(IntoIterator::into_iter([(1, 1.5), (2, 2.5)])),
+    );
+    drop(columns);
+
+    // Owned fields share the key cache of rows with the same value types
+    assert_eq!(IntFloatSnapshot::keys(&table).count(), 2);
+
+    let columns = IntFloatSnapshot::read_columns(&table);
+    let snapshot = IntFloatSnapshot::from_row(&mut IntFloatSnapshot::get_row(&table, &columns, &1));
+    assert_eq!(snapshot, IntFloatSnapshot { int: 2, float: 2.5 });
+
+    // The inner guards are released as soon as the values are cloned
+    let ints = Column::<usize, u32>::read_cell_map(&table);
+    *CellMap::write_cell(&*ints, &1).unwrap() = 3;
+    drop(ints);
+    assert_eq!(
+        IntFloatSnapshot::try_get_row(&table, &columns, &1),
+        Ok((3, 2.5))
+    );
+    assert_eq!(snapshot.int, 2);
+    drop(columns);
+
+    // So the snapshot can outlive the table borrow
+    let snapshot = std::thread::spawn(move || snapshot).join().unwrap();
+    assert_eq!(snapshot.int, 2);
+}
+
+#[test]
+fn test_two_level_columns() {
+    let table = TallyTable::default();
+
+    let mut columns = IntFloatRow::write_columns(&table);
+    IntFloatRow::extend(
+        &table,
+        &mut columns,
+        NextKeyIterator::new(&table).zip((0..4).map(|i| (i, i as f32))),
+    );
+
+    // Values are borrowed straight out of the write-locked cell maps
+    for key in IntFloatRow::snapshot_keys(&table) {
+        let mut row = IntFloatRow::get_row_mut(&table, &mut columns, &key);
+        let row = IntFloatRow::from_row(&mut row);
+        *row.float += *row.int as f32;
+    }
+    assert_eq!(
+        IntFloatRow::try_get_row_mut(&table, &mut columns, &4).err(),
+        Some(KeyError::Missing)
+    );
+    drop(columns);
+    assert_eq!(table.floats.read().unwrap()[&3], 6.0);
+
+    // Through read guards they're shared, so any number of rows can borrow them at once
+    let columns = IntFloatRow::read_columns(&table);
+    let (int, float) = IntFloatRow::get_row(&table, &columns, &2);
+    let (_, same_float) = IntFloatRow::get_row(&table, &columns, &2);
+    assert_eq!((*int, *float, *same_float), (2, 4.0, 4.0));
+    drop(columns);
+
+    let mut floats = Column::<usize, f32>::write_cell_map(&table);
+    *KeyValueMap::get_mut(&mut *floats, &1).unwrap() *= 10.0;
+    drop(floats);
+    assert_eq!(table.floats.read().unwrap()[&1], 20.0);
+
+    let mut ints = Column::<usize, u32>::write_cell_map(&table);
+    assert_eq!(KeyValueMap::remove(&mut *ints, &0), Some(0));
+    drop(ints);
+    assert_eq!(table.ints.borrow().len(), 3);
+
+    let result = Query::parse("SELECT floats FROM TallyTable WHERE floats > 3")
+        .and_then(|query| query.run(&[&table]))
+        .unwrap();
+    assert_eq!(
+        result.rows(),
+        [
+            (Value::UInt(1), vec![Value::Float(20.0)]),
+            (Value::UInt(2), vec![Value::Float(4.0)]),
+            (Value::UInt(3), vec![Value::Float(6.0)]),
+        ]
+    );
+
+    // Despawning hands back the bare values of two-level columns
+    let mut despawned = table.despawn(&3);
+    assert_eq!(despawned.take::<u32>("ints"), Some(3));
+    assert_eq!(despawned.take::<f32>("floats"), Some(6.0));
+
+    // Any cell map can hold bare values
+    let table = LocalTable::default();
+    let mut columns = IntFloatRow::write_columns(&table);
+    IntFloatRow::insert(&table, &mut columns, 0, (1, 1.0));
+    let mut row = IntFloatRow::get_row_mut(&table, &mut columns, &0);
+    *IntFloatRow::from_row(&mut row).float += 1.0;
+    drop(columns);
+    assert_eq!(KeyValueMap::get(&*table.floats.borrow(), &0), Some(&2.0));
+}
+
+#[test]
+fn test_insert_struct() {
+    let table = CharacterTable::default();
+
+    // Named values convert into the row's Insert tuple
+    let mut columns = CharacterRow::write_columns(&table);
+    CharacterRow::insert(
+        &table,
+        &mut columns,
+        0,
+        CharacterRowInsert {
+            health: 100,
+            armor: 5,
+            name: "knight".to_string(),
+        },
+    );
+
+    // #[default] fields can be left out
+    CharacterRow::extend(
+        &table,
+        &mut columns,
+        (1..3).map(|key| {
+            (
+                key,
+                CharacterRowInsert::new(50 * key as u32, "rogue".to_string()),
+            )
+        }),
+    );
+    drop(columns);
+
+    let columns = CharacterRow::read_columns(&table);
+    assert_eq!(
+        CharacterRow::try_get_row(&table, &columns, &2).map(|(health, armor, name)| (
+            *health,
+            *armor,
+            name.clone()
+        )),
+        Ok((100, 0, "rogue".to_string()))
+    );
+    drop(columns);
+
+    // Transactions take them too
+    let table = Table::default();
+    let mut transaction = Transaction::<_, _, IntFloatRow>::new(&table);
+    transaction
+        .insert(0, IntFloatRowInsert { int: 1, float: 1.5 })
+        .update(0, IntFloatRowInsert::new(2, 2.5));
+    transaction.commit().unwrap();
+
+    let columns = IntFloatRow::read_columns(&table);
+    let mut row = IntFloatRow::get_row(&table, &columns, &0);
+    assert_eq!(*IntFloatRow::from_row(&mut row).float, 2.5);
+}
+
+#[test]
+fn test_flatten() {
+    let table = CharacterTable::default();
+
+    // Flattened rows nest their values in the outer row's insert
+    let mut columns = NamedStatsRow::write_columns(&table);
+    NamedStatsRow::insert(
+        &table,
+        &mut columns,
+        0,
+        NamedStatsRowInsert {
+            name: "knight".to_string(),
+            stats: StatsRowInsert {
+                health: 100,
+                armor: 5,
+            },
+        },
+    );
+    NamedStatsRow::extend(
+        &table,
+        &mut columns,
+        IntoIterator::into_iter([(1, ("rogue".to_string(), (60, 1)))]),
+    );
+    drop(columns);
+
+    // The flattened row is keyed alongside the outer one
+    assert_eq!(NamedStatsRow::keys(&table).collect::<Vec<_>>(), vec![0, 1]);
+    assert_eq!(StatsRow::keys(&table).collect::<Vec<_>>(), vec![0, 1]);
+
+    let columns = NamedStatsRow::read_columns(&table);
+    for key in NamedStatsRow::keys(&table) {
+        let mut guards = NamedStatsRow::get_row(&table, &columns, &key);
+        let row = NamedStatsRow::from_row(&mut guards);
+        *row.stats.health -= *row.stats.armor;
+    }
+
+    let mut guards = NamedStatsRow::try_get_row(&table, &columns, &1).unwrap();
+    let row = NamedStatsRow::from_row(&mut guards);
+    assert_eq!((row.name.as_str(), *row.stats.health), ("rogue", 59));
+    drop(guards);
+    drop(columns);
+
+    // Removing the outer row removes the flattened columns too
+    let mut transaction = Transaction::<_, _, NamedStatsRow>::new(&table);
+    transaction.remove(0);
+    transaction.commit().unwrap();
+
+    assert_eq!(StatsRow::keys(&table).collect::<Vec<_>>(), vec![1]);
+    let health = Column::<usize, Health>::read_cell_map(&table);
+    assert!(CellMap::read_cell(&*health, &0).is_none());
+}
+
+#[test]
+fn test_foreign_keys() {
+    let characters = Table::default();
+    let items = ItemTable::default();
+
+    let mut columns = IntFloatRow::write_columns(&characters);
+    IntFloatRow::extend(
+        &characters,
+        &mut columns,
+        NextKeyIterator::new(&characters).zip((0..3).map(|i| (i, i as f32))),
+    );
+    drop(columns);
+
+    // Inserts must reference an existing parent
+    let owners = Reference::<_, _, IntFloatRow, _, _, OwnedItemRow, ForeignKey<usize>>::new(
+        &characters,
+        &items,
+        OnRemove::Restrict,
+    );
+    assert!(owners.insert(0, (ForeignKey(0), (1.5,))).is_ok());
+    assert_eq!(
+        owners.insert(1, (ForeignKey(9), (2.5,))).err(),
+        Some(ReferenceError::MissingParent(9))
+    );
+    assert!(!OwnedItemRow::contains_key(&items, &1));
+
+    let item_columns = OwnedItemRow::read_columns(&items);
+    let mut guards = OwnedItemRow::get_row(&items, &item_columns, &0);
+    let row = OwnedItemRow::from_row(&mut guards);
+    assert_eq!((*row.owner, *row.item.weight), (ForeignKey(0), 1.5));
+    drop(guards);
+    drop(item_columns);
+
+    // Restrict refuses to remove referenced parents
+    assert_eq!(
+        owners.remove(&0).err(),
+        Some(ReferenceError::Restricted {
+            parent: 0,
+            child: 0
+        })
+    );
+    assert!(owners.remove(&1).is_ok());
+    assert_eq!(
+        owners.remove(&1).err(),
+        Some(ReferenceError::MissingParent(1))
+    );
+
+    // Cascade despawns the referencing rows along with their parent, including columns outside the child row
+    KeyValueMap::insert(
+        &mut *Column::<usize, Option<ForeignKey<usize>>>::write_cell_map(&items),
+        0,
+        RwLock::new(None),
+    );
+    let owners = Reference::<_, _, IntFloatRow, _, _, OwnedItemRow, ForeignKey<usize>>::new(
+        &characters,
+        &items,
+        OnRemove::Cascade,
+    );
+    assert_eq!(owners.referencing(&0), vec![0]);
+    assert!(owners.remove(&0).is_ok());
+    assert!(OwnedItemRow::keys(&items).next().is_none());
+    assert!(owners.referencing(&0).is_empty());
+    assert!(KeyValueMap::get(
+        &*Column::<usize, Option<ForeignKey<usize>>>::read_cell_map(&items),
+        &0
+    )
+    .is_none());
+
+    // Set-null clears references in optional columns
+    let holders = Reference::<_, _, IntFloatRow, _, _, HeldItemRow, Option<ForeignKey<usize>>>::new(
+        &characters,
+        &items,
+        OnRemove::SetNull,
+    );
+    assert!(holders.insert(2, (Some(ForeignKey(2)), (3.5,))).is_ok());
+    assert!(holders.insert(3, (None, (4.5,))).is_ok());
+
+    assert!(holders.remove(&2).is_ok());
+    assert_eq!(IntFloatRow::keys(&characters).count(), 0);
+    assert_eq!(HeldItemRow::keys(&items).collect::<Vec<_>>(), vec![2, 3]);
+
+    let item_columns = HeldItemRow::read_columns(&items);
+    let mut guards = HeldItemRow::get_row(&items, &item_columns, &2);
+    let row = HeldItemRow::from_row(&mut guards);
+    assert_eq!((*row.holder, *row.item.weight), (None, 3.5));
+}
+
+#[test]
+fn test_join() {
+    let users = Table::default();
+    let items = ItemTable::default();
+
+    let mut columns = IntFloatRow::write_columns(&users);
+    IntFloatRow::extend(
+        &users,
+        &mut columns,
+        NextKeyIterator::new(&users).zip((0..4).map(|i| (i, i as f32))),
+    );
+    drop(columns);
+
+    let mut columns = OwnedItemRow::write_columns(&items);
+    OwnedItemRow::extend(
+        &items,
+        &mut columns,
+        NextKeyIterator::new(&items).zip(IntoIterator::into_iter([
+            (ForeignKey(0), (1.5,)),
+            (ForeignKey(0), (2.5,)),
+            (ForeignKey(2), (3.5,)),
+        ])),
+    );
+    drop(columns);
+
+    let user_columns = IntFloatRow::read_columns(&users);
+    let item_columns = OwnedItemRow::read_columns(&items);
+    let weight_columns = WeightRow::read_columns(&items);
+
+    // Rows under the same key, driven by the smaller weight key set for inner joins
+    let by_key = |kind| {
+        Join::<_, _, IntFloatRow, _, _, WeightRow>::new(
+            &users,
+            &user_columns,
+            &items,
+            &weight_columns,
+            kind,
+        )
+        .on_key()
+        .map(|(user, weight)| (*user.0, weight.map(|(weight,)| *weight)))
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        by_key(JoinKind::Inner),
+        vec![(0, Some(1.5)), (1, Some(2.5)), (2, Some(3.5))]
+    );
+    assert_eq!(by_key(JoinKind::Left)[3], (3, None));
+
+    // Items with the user they reference
+    let owners = Join::<_, _, OwnedItemRow, _, _, IntFloatRow>::new(
+        &items,
+        &item_columns,
+        &users,
+        &user_columns,
+        JoinKind::Inner,
+    )
+    .on_left_foreign_key()
+    .map(|((_, (weight,)), user)| (*weight, user.map(|(int, _)| *int)))
+    .collect::<Vec<_>>();
+    assert_eq!(owners, vec![(1.5, Some(0)), (2.5, Some(0)), (3.5, Some(2))]);
+
+    // Users with each item referencing them, then those without any for left joins
+    let by_owner = |kind| {
+        Join::<_, _, IntFloatRow, _, _, OwnedItemRow>::new(
+            &users,
+            &user_columns,
+            &items,
+            &item_columns,
+            kind,
+        )
+        .on_right_foreign_key()
+        .map(|(user, item)| (*user.0, item.map(|(_, (weight,))| *weight)))
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        by_owner(JoinKind::Inner),
+        vec![(0, Some(1.5)), (0, Some(2.5)), (2, Some(3.5))]
+    );
+    assert_eq!(by_owner(JoinKind::Left)[3..], [(1, None), (3, None)]);
+}
+
+#[test]
+fn test_despawn() {
+    let table = CharacterTable::default();
+
+    let mut columns = CharacterRow::write_columns(&table);
+    CharacterRow::extend(
+        &table,
+        &mut columns,
+        NextKeyIterator::new(&table).zip(IntoIterator::into_iter([
+            (100, 5, "knight".to_string()),
+            (60, 1, "rogue".to_string()),
+        ])),
+    );
+    drop(columns);
+
+    let mut columns = StatsRow::write_columns(&table);
+    StatsRow::insert(&table, &mut columns, 2, (80, 3));
+    drop(columns);
+
+    // Every column and row cache loses the key, not just those of one row type
+    let mut despawned = table.despawn(&0);
+    assert_eq!(*despawned.key(), 0);
+    assert_eq!(
+        despawned.columns().collect::<Vec<_>>(),
+        vec!["health", "armor", "name"]
+    );
+    assert_eq!(
+        despawned
+            .take::<RwLock<String>>("name")
+            .map(|name| name.into_inner().unwrap()),
+        Some("knight".to_string())
+    );
+    assert!(despawned.get::<RwLock<String>>("health").is_none());
+    assert_eq!(
+        despawned
+            .get::<RwLock<u32>>("health")
+            .map(|health| *health.read().unwrap()),
+        Some(100)
+    );
+
+    assert_eq!(CharacterRow::keys(&table).collect::<Vec<_>>(), vec![1]);
+    let health = Column::<usize, Health>::read_cell_map(&table);
+    assert!(CellMap::read_cell(&*health, &0).is_none());
+    drop(health);
+
+    // Keys only held by some columns are removed from those
+    assert_eq!(table.despawn(&2).len(), 2);
+    assert!(StatsRow::keys(&table).next().is_none());
+    assert!(table.despawn(&2).is_empty());
+
+    // Generational keys are freed, so despawned keys go stale
+    let entities = EntityTable::default();
+    let entity = entities.next_key();
+    let mut columns = IntFloatRow::write_columns(&entities);
+    IntFloatRow::insert(&entities, &mut columns, entity, (1, 1.0));
+    drop(columns);
+
+    assert_eq!(entities.despawn(&entity).len(), 2);
+    assert!(NextKey::is_stale(&entities, &entity));
+}
+
+#[test]
+fn test_aggregate() {
+    let table = CharacterTable::default();
+
+    let mut columns = CharacterRow::write_columns(&table);
+    CharacterRow::extend(
+        &table,
+        &mut columns,
+        NextKeyIterator::new(&table).zip(IntoIterator::into_iter([
+            (100, 5, "knight".to_string()),
+            (60, 1, "rogue".to_string()),
+            (80, 5, "paladin".to_string()),
+            (40, 1, "bard".to_string()),
+        ])),
+    );
+    drop(columns);
+
+    // Single columns
+    let health = Column::<usize, Health>::read_cell_map(&table);
+    let armor = Column::<usize, Armor>::read_cell_map(&table);
+    let values = || health.cells().map(|(_, health)| *health);
+    assert_eq!(health.cells().count(), 4);
+    assert_eq!(values().sum::<u32>(), 280);
+    assert_eq!(values().min(), Some(40));
+    assert_eq!(values().max(), Some(100));
+    assert_eq!(values().mean(), Some(70.0));
+    assert_eq!(
+        health
+            .cells()
+            .fold(0, |acc, (key, health)| acc + *key as u32 * *health),
+        60 + 160 + 120
+    );
+
+    // Grouped by a second column's values
+    let by_armor = health.cells().group_by(
+        |(key, _)| CellMap::read_cell(&*armor, key).map(|armor| *armor),
+        || 0,
+        |sum, (_, health)| sum + *health,
+    );
+    assert_eq!(by_armor, BTreeMap::from([(1, 100), (5, 180)]));
+    drop((health, armor));
+
+    // Floats, skipping NaN
+    let items = ItemTable::default();
+    let mut columns = WeightRow::write_columns(&items);
+    WeightRow::extend(
+        &items,
+        &mut columns,
+        NextKeyIterator::new(&items).zip(IntoIterator::into_iter([(2.5,), (f32::NAN,), (0.5,)])),
+    );
+    drop(columns);
+
+    let weights = Column::<usize, f32>::read_cell_map(&items);
+    assert_eq!(weights.cells().map(|(_, w)| *w).partial_min(), Some(0.5));
+    assert_eq!(weights.cells().map(|(_, w)| *w).partial_max(), Some(2.5));
+    assert!(std::iter::empty::<f32>().mean().is_none());
+    assert_eq!(
+        IntoIterator::into_iter([u64::MAX, 0]).mean(),
+        Some(u64::MAX as f64 / 2.0)
+    );
+    assert_eq!(IntoIterator::into_iter([3usize, 4]).mean(), Some(3.5));
+    drop(weights);
+
+    // Rows
+    let columns = CharacterRow::read_columns(&table);
+    let names = CharacterRow::rows(&table, &columns).group_by(
+        |(_, (_, armor, _))| Some(**armor),
+        Vec::new,
+        |mut names, (_, (_, _, name))| {
+            names.push(name.clone());
+            names
+        },
+    );
+    assert_eq!(names[&1], vec!["rogue".to_string(), "bard".to_string()]);
+    assert_eq!(
+        CharacterRow::rows(&table, &columns)
+            .map(|(_, (health, _, _))| *health)
+            .partial_max(),
+        Some(100)
+    );
+}
+
+#[test]
+fn test_query() {
+    let table = Table::default();
+
+    let mut columns = IntFloatRow::write_columns(&table);
+    IntFloatRow::extend(
+        &table,
+        &mut columns,
+        NextKeyIterator::new(&table).zip((0..4).map(|i| (i * 10, i as f32 / 2.0))),
+    );
+    drop(columns);
+
+    let mut columns = CharStrRow::write_columns(&table);
+    CharStrRow::insert(&table, &mut columns, 4, ('a', "it's".into()));
+    drop(columns);
+
+    let run = |text: &str| Query::parse(text).and_then(|query| query.run(&[&table]));
+
+    let result =
+        run("SELECT ints, floats FROM Table WHERE ints > 10 ORDER BY floats DESC").unwrap();
+    assert_eq!(result.columns(), ["ints", "floats"]);
+    assert_eq!(
+        result.rows(),
+        [
+            (Value::UInt(3), vec![Value::UInt(30), Value::Float(1.5)]),
+            (Value::UInt(2), vec![Value::UInt(20), Value::Float(1.0)]),
+        ]
+    );
+    assert_eq!(
+        result.to_string(),
+        "key | ints | floats\n\
+         ----+------+-------\n\
+         3   | 30   | 1.5\n\
+         2   | 20   | 1\n"
+    );
+
+    // Rows are the keys held by every column the query reads, including those it only filters by
+    let result = run("select chars, strs from Table where strs = 'it''s'").unwrap();
+    assert_eq!(
+        result.rows(),
+        [(
+            Value::UInt(4),
+            vec![Value::Text("a".into()), Value::Text("it's".into())]
+        )]
+    );
+    assert!(run("SELECT chars FROM Table WHERE ints >= 0")
+        .unwrap()
+        .rows()
+        .is_empty());
+
+    let result =
+        run("SELECT ints, floats FROM Table WHERE NOT (ints < 10 OR floats >= 1.5) LIMIT 1")
+            .unwrap();
+    assert_eq!(
+        result.rows(),
+        [(Value::UInt(1), vec![Value::UInt(10), Value::Float(0.5)])]
+    );
+
+    let result = run("SELECT * FROM Table").unwrap();
+    assert_eq!(result.columns().len(), 10);
+    assert!(result.rows().is_empty());
+
+    // Errors point at the offending token
+    let error = |text: &str| {
+        let QueryError { message, span } = run(text).unwrap_err();
+        (message, text[span].to_string())
+    };
+    assert_eq!(
+        error("SELECT ints FROM Table WHERE health > 1"),
+        (
+            "Unknown column `health` in table `Table`".to_string(),
+            "health".to_string()
+        )
+    );
+    assert_eq!(
+        error("SELECT ints FROM Table ORDER floats"),
+        (
+            "Expected BY, found `floats`".to_string(),
+            "floats".to_string()
+        )
+    );
+    assert_eq!(
+        error("SELECT ints FROM Table WHERE ints = 'ten'"),
+        (
+            "Cannot compare integer with text".to_string(),
+            "=".to_string()
+        )
+    );
+    assert_eq!(
+        error("SELECT ints FROM Tables").0,
+        "Unknown table `Tables`".to_string()
+    );
+
+    // Deeply nested conditions are refused rather than overflowing the stack
+    let nested = format!("SELECT * FROM Table WHERE {}flags", "NOT ".repeat(100_000));
+    let QueryError { message, span } = Query::parse(&nested).unwrap_err();
+    assert_eq!(message, "Conditions can't nest more than 128 deep");
+    assert_eq!(
+        span.start,
+        "SELECT * FROM Table WHERE ".len() + 128 * "NOT ".len()
+    );
+
+    let nested = format!("SELECT * FROM Table WHERE {}flags", "(".repeat(100_000));
+    assert!(Query::parse(&nested).is_err());
+
+    let chained = format!(
+        "SELECT * FROM Table WHERE flags{}",
+        " AND flags".repeat(100_000)
+    );
+    assert_eq!(&chained[Query::parse(&chained).unwrap_err().span], "AND");
+    assert!(Query::parse(&format!(
+        "SELECT * FROM Table WHERE {}flags",
+        "NOT ".repeat(128)
+    ))
+    .is_ok());
+    assert_eq!(
+        run("SELECT ints FROM Table WHERE ints ; 1")
+            .unwrap_err()
+            .render("SELECT ints FROM Table WHERE ints ; 1"),
+        "SELECT ints FROM Table WHERE ints ; 1\n\
+         \x20                                 ^ Unexpected character `;`"
+    );
+}
+
+#[test]
+fn test_view() {
+    let table = CharacterTable::default();
+
+    let mut columns = StatsRow::write_columns(&table);
+    StatsRow::extend(
+        &table,
+        &mut columns,
+        NextKeyIterator::new(&table).zip(IntoIterator::into_iter([(100, 5), (60, 1)])),
+    );
+    drop(columns);
+
+    // Built from the existing base rows
+    let view = View::<_, StatsRow, _>::new(&table, HealthByArmor);
+    assert!(view.check().is_empty());
+
+    // Then maintained from the rows the table publishes, whether committed through transactions
+    let mut transaction = Transaction::<_, _, StatsRow>::new(&table);
+    transaction
+        .insert(2, (80, 5))
+        .insert(3, (40, 2))
+        .update(1, (50, 1));
+    transaction.commit().unwrap();
+
+    let totals = |view: &View<_, StatsRow, HealthByArmor>| {
+        let columns = TotalRow::read_columns(view);
+        TotalRow::keys(view)
+            .map(|armor| {
+                let mut guards = TotalRow::get_row(view, &columns, &armor);
+                (armor, *TotalRow::from_row(&mut guards).total)
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(totals(&view), vec![(1, 50), (2, 40), (5, 180)]);
+
+    // Or inserted and removed through rows directly, with view rows removed along with their last contribution
+    let mut columns = StatsRow::write_columns(&table);
+    StatsRow::remove(&table, &mut columns, &3);
+    StatsRow::insert(&table, &mut columns, 0, (100, 1));
+    drop(columns);
+    assert_eq!(totals(&view), vec![(1, 150), (5, 80)]);
+    assert!(view.check().is_empty());
+
+    // Despawned rows are published too
+    let mut columns = StatsRow::write_columns(&table);
+    StatsRow::insert(&table, &mut columns, 4, (10, 7));
+    drop(columns);
+    assert_eq!(totals(&view)[2], (7, 10));
+    table.despawn(&4);
+    assert_eq!(totals(&view), vec![(1, 150), (5, 80)]);
+
+    // Rows flattening the view's row publish once per key, after every cell is written
+    let subscription = table.subscribe();
+    let mut columns = NamedStatsRow::write_columns(&table);
+    NamedStatsRow::insert(&table, &mut columns, 6, ("ogre".to_string(), (30, 9)));
+    drop(columns);
+    assert_eq!(subscription.take(), vec![Event::Insert(6)]);
+    assert_eq!(totals(&view)[2], (9, 30));
+
+    // Removing a key through another row type leaves the view's row in place
+    let mut columns = NameRow::write_columns(&table);
+    NameRow::remove(&table, &mut columns, &6);
+    drop(columns);
+    assert_eq!(subscription.take(), vec![Event::Remove(6)]);
+    assert_eq!(totals(&view)[2], (9, 30));
+    assert!(view.check().is_empty());
+
+    let mut columns = StatsRow::write_columns(&table);
+    StatsRow::remove(&table, &mut columns, &6);
+    drop(columns);
+    assert_eq!(totals(&view), vec![(1, 150), (5, 80)]);
+
+    // Writes through existing cells aren't published, so show up as inconsistencies
+    let armor = Column::<usize, Armor>::read_cell_map(&table);
+    *CellMap::write_cell(&*armor, &2).unwrap() = 3;
+    drop(armor);
+
+    assert_eq!(
+        view.check(),
+        vec![
+            Inconsistency::Missing {
+                key: 3,
+                expected: 80
+            },
+            Inconsistency::Unexpected { key: 5, found: 80 },
+        ]
+    );
+
+    view.rebuild();
+    assert!(view.check().is_empty());
+    assert_eq!(totals(&view), vec![(1, 150), (3, 80)]);
+}
@@ -0,0 +1,132 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec,
+    vec::Vec,
+};
+use core::{
+    any::TypeId,
+    cell::RefCell,
+    ops::{Deref, DerefMut},
+    sync::atomic::AtomicUsize,
+};
+
+use crate::{
+    BitSet, CellMap, FromRow, GenerationalKeys, GenerationalMap, KeyError, KeySet, KeySetAlgebra,
+    NextKey, NextKeyIterator, Row, SpinLock, Transaction, TransactionError,
+};
+
+// Tests that only need core and alloc, so they also run without the std feature:
+//   cargo test -p database_api --no-default-features
+
+#[derive(Debug, Default, crate::macros::Column, crate::macros::Table)]
+pub struct AllocTable {
+    #[primary_key]
+    primary_key: AtomicUsize,
+
+    #[key_cache]
+    key_cache: SpinLock<BTreeMap<TypeId, SpinLock<BTreeSet<usize>>>>,
+
+    ints: SpinLock<BTreeMap<usize, SpinLock<u32>>>,
+    floats: RefCell<BTreeMap<usize, RefCell<f32>>>,
+}
+
+#[derive(Debug, crate::macros::Row)]
+pub struct AllocRow<'a> {
+    int: &'a u32,
+    float: &'a mut f32,
+}
+
+impl<'a, T1, T2> FromRow<'a, (T1, T2)> for AllocRow<'a>
+where
+    T1: Deref<Target = u32>,
+    T2: DerefMut<Target = f32>,
+{
+    fn from_row((int, float): &'a mut (T1, T2)) -> Self {
+        AllocRow {
+            int: Deref::deref(int),
+            float: DerefMut::deref_mut(float),
+        }
+    }
+}
+
+#[test]
+fn test_alloc_rows() {
+    let table = AllocTable::default();
+
+    let mut columns = AllocRow::write_columns(&table);
+    AllocRow::extend(
+        &table,
+        &mut columns,
+        NextKeyIterator::new(&table).zip((0..4).map(|i| (i, i as f32))),
+    );
+    drop(columns);
+
+    let columns = AllocRow::read_columns(&table);
+    for key in AllocRow::keys(&table) {
+        let mut row = AllocRow::get_row(&table, &columns, &key);
+        let row = AllocRow::from_row(&mut row);
+        *row.float += 0.5;
+        assert_eq!(*row.float, *row.int as f32 + 0.5);
+    }
+    drop(columns);
+
+    let mut columns = AllocRow::write_columns(&table);
+    AllocRow::remove(&table, &mut columns, &2);
+    drop(columns);
+
+    assert_eq!(AllocRow::keys(&table).collect::<Vec<_>>(), vec![0, 1, 3]);
+
+    let columns = AllocRow::read_columns(&table);
+    assert_eq!(
+        AllocRow::try_get_row(&table, &columns, &2).err(),
+        Some(KeyError::Missing)
+    );
+}
+
+#[test]
+fn test_alloc_transaction() {
+    let table = AllocTable::default();
+
+    let mut transaction = Transaction::<_, _, AllocRow>::new(&table);
+    transaction.insert(0, (10, 10.0)).insert(1, (20, 20.0));
+    transaction.commit().unwrap();
+
+    // Without std, failed transactions still roll back
+    let mut transaction = Transaction::<_, _, AllocRow>::new(&table);
+    transaction.remove(0).update(2, (30, 30.0));
+    assert_eq!(transaction.commit(), Err(TransactionError::MissingKey(2)));
+
+    assert_eq!(AllocRow::keys(&table).collect::<Vec<_>>(), vec![0, 1]);
+}
+
+#[test]
+fn test_alloc_generational_keys() {
+    let keys = GenerationalKeys::default();
+    let mut map = GenerationalMap::<_, SpinLock<u32>>::default();
+
+    let first = keys.next_key();
+    CellMap::insert(&mut map, first, 1);
+    CellMap::remove(&mut map, &first);
+    keys.free_key(&first);
+
+    let second = keys.next_key();
+    CellMap::insert(&mut map, second, 2);
+    assert!(keys.is_stale(&first));
+    assert_eq!(
+        CellMap::try_read_cell(&map, &first).err(),
+        Some(KeyError::Stale)
+    );
+    assert_eq!(*CellMap::try_read_cell(&map, &second).unwrap(), 2);
+}
+
+#[test]
+fn test_alloc_bit_set() {
+    let mut evens = (0..100).step_by(2).collect::<BitSet>();
+    let threes = (0..100).step_by(3).collect::<BTreeSet<usize>>();
+
+    assert_eq!(evens.intersection(&threes).count(), 17);
+    evens.difference_with(&threes);
+    assert_eq!(evens.len(), 50 - 17);
+    evens.union_with(&(0..100).step_by(3).collect::<BitSet>());
+    assert_eq!(evens.len(), 50 + 34 - 17);
+}
@@ -1,7 +1,7 @@
-use std::{
-    collections::{BTreeSet, HashSet},
-    hash::Hash,
-};
+use alloc::collections::BTreeSet;
+
+#[cfg(feature = "std")]
+use std::{collections::HashSet, hash::Hash};
 
 /// A set collection type.
 pub trait KeySet<'a, K>
@@ -18,20 +18,147 @@ where
     fn remove(&mut self, key: &K) -> bool;
 
     fn iter(&'a self) -> Self::Iter;
+
+    fn len(&'a self) -> usize {
+        self.iter().count()
+    }
+
+    fn is_empty(&'a self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// Iterate over the keys present in both `self` and `other`.
+    fn intersection<'b, S>(&'a self, other: &'b S) -> Intersection<'b, Self::Iter, S>
+    where
+        S: KeySet<'b, K>,
+        K: 'b,
+    {
+        Intersection {
+            iter: self.iter(),
+            other,
+        }
+    }
+
+    /// Iterate over the keys present in either `self` or `other`, without duplicates.
+    fn union<'b, S>(&'a self, other: &'b S) -> Union<'a, Self::Iter, S::Iter, Self>
+    where
+        S: KeySet<'b, K>,
+        K: 'b,
+    {
+        Union {
+            first: self.iter(),
+            second: other.iter(),
+            first_set: self,
+        }
+    }
+
+    /// Iterate over the keys present in `self` but not in `other`.
+    fn difference<'b, S>(&'a self, other: &'b S) -> Difference<'b, Self::Iter, S>
+    where
+        S: KeySet<'b, K>,
+        K: 'b,
+    {
+        Difference {
+            iter: self.iter(),
+            other,
+        }
+    }
+}
+
+/// In-place set algebra between a [KeySet] and another set of type `Rhs`.
+///
+/// Implemented for [BTreeSet] and [HashSet] against any [KeySet],
+/// and for [BitSet](crate::BitSet) against the set types it can combine with,
+/// which is word by word when both sides are bitsets.
+pub trait KeySetAlgebra<'b, Rhs> {
+    /// Remove any keys not present in `other`.
+    fn intersect_with(&mut self, other: &'b Rhs);
+
+    /// Insert all keys present in `other`.
+    fn union_with(&mut self, other: &'b Rhs);
+
+    /// Remove any keys present in `other`.
+    fn difference_with(&mut self, other: &'b Rhs);
+}
+
+/// Iterator over the keys present in both of two [KeySet]s. See [KeySet::intersection].
+pub struct Intersection<'b, I, S> {
+    iter: I,
+    other: &'b S,
+}
+
+impl<'a, 'b, I, S, K> Iterator for Intersection<'b, I, S>
+where
+    I: Iterator<Item = &'a K>,
+    S: KeySet<'b, K>,
+    K: 'a + 'b,
+{
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let other = self.other;
+        self.iter.find(|key| other.contains(key))
+    }
+}
+
+/// Iterator over the keys present in either of two [KeySet]s. See [KeySet::union].
+pub struct Union<'a, I, J, S: ?Sized> {
+    first: I,
+    second: J,
+    first_set: &'a S,
+}
+
+impl<'a, I, J, S, K> Iterator for Union<'a, I, J, S>
+where
+    I: Iterator<Item = &'a K>,
+    J: Iterator<Item = &'a K>,
+    S: KeySet<'a, K> + ?Sized,
+    K: 'a,
+{
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(key) = self.first.next() {
+            return Some(key);
+        }
+
+        let first_set = self.first_set;
+        self.second.find(|key| !first_set.contains(key))
+    }
+}
+
+/// Iterator over the keys present in one [KeySet] but not another. See [KeySet::difference].
+pub struct Difference<'b, I, S> {
+    iter: I,
+    other: &'b S,
+}
+
+impl<'a, 'b, I, S, K> Iterator for Difference<'b, I, S>
+where
+    I: Iterator<Item = &'a K>,
+    S: KeySet<'b, K>,
+    K: 'a + 'b,
+{
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let other = self.other;
+        self.iter.find(|key| !other.contains(key))
+    }
 }
 
 impl<'a, K> KeySet<'a, K> for BTreeSet<K>
 where
     K: Ord + 'a,
 {
-    type Iter = std::collections::btree_set::Iter<'a, K>;
+    type Iter = alloc::collections::btree_set::Iter<'a, K>;
 
     fn insert(&mut self, key: K) -> bool {
         BTreeSet::insert(self, key)
     }
 
     fn extend(&mut self, values: impl Iterator<Item = K>) {
-        std::iter::Extend::extend(self, values)
+        core::iter::Extend::extend(self, values)
     }
 
     fn contains(&self, key: &K) -> bool {
@@ -45,8 +172,35 @@ where
     fn iter(&'a self) -> Self::Iter {
         BTreeSet::iter(self)
     }
+
+    fn len(&'a self) -> usize {
+        BTreeSet::len(self)
+    }
+
+    fn is_empty(&'a self) -> bool {
+        BTreeSet::is_empty(self)
+    }
+}
+
+impl<'b, K, S> KeySetAlgebra<'b, S> for BTreeSet<K>
+where
+    K: Ord + Clone + 'b,
+    S: KeySet<'b, K>,
+{
+    fn intersect_with(&mut self, other: &'b S) {
+        self.retain(|key| other.contains(key))
+    }
+
+    fn union_with(&mut self, other: &'b S) {
+        core::iter::Extend::extend(self, other.iter().cloned())
+    }
+
+    fn difference_with(&mut self, other: &'b S) {
+        self.retain(|key| !other.contains(key))
+    }
 }
 
+#[cfg(feature = "std")]
 impl<'a, K> KeySet<'a, K> for HashSet<K>
 where
     K: Hash + Eq + 'a,
@@ -72,4 +226,31 @@ where
     fn iter(&'a self) -> Self::Iter {
         HashSet::iter(self)
     }
+
+    fn len(&'a self) -> usize {
+        HashSet::len(self)
+    }
+
+    fn is_empty(&'a self) -> bool {
+        HashSet::is_empty(self)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'b, K, S> KeySetAlgebra<'b, S> for HashSet<K>
+where
+    K: Hash + Eq + Clone + 'b,
+    S: KeySet<'b, K>,
+{
+    fn intersect_with(&mut self, other: &'b S) {
+        self.retain(|key| other.contains(key))
+    }
+
+    fn union_with(&mut self, other: &'b S) {
+        std::iter::Extend::extend(self, other.iter().cloned())
+    }
+
+    fn difference_with(&mut self, other: &'b S) {
+        self.retain(|key| !other.contains(key))
+    }
 }
@@ -1,8 +1,32 @@
+use alloc::collections::BTreeMap;
+
+#[cfg(feature = "std")]
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::HashMap,
     hash::{BuildHasher, Hash},
 };
 
+/// The reason a key lookup failed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum KeyError {
+    /// Nothing is stored under the key.
+    Missing,
+    /// The key refers to an entity that has since been freed.
+    Stale,
+}
+
+impl core::fmt::Display for KeyError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            KeyError::Missing => write!(f, "Missing key"),
+            KeyError::Stale => write!(f, "Stale key"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for KeyError {}
+
 /// A key-value map type.
 pub trait KeyValueMap<'a, K, V>
 where
@@ -13,9 +37,21 @@ where
     fn insert(&mut self, key: K, value: V) -> Option<V>;
     fn extend(&mut self, values: impl Iterator<Item = (K, V)>);
 
+    /// Like [KeyValueMap::insert], but refuses keys that are stale
+    /// for maps that can tell the difference.
+    fn try_insert(&mut self, key: K, value: V) -> Result<Option<V>, KeyError> {
+        Ok(self.insert(key, value))
+    }
+
     fn get(&self, key: &K) -> Option<&V>;
     fn get_mut(&mut self, key: &K) -> Option<&mut V>;
 
+    /// Like [KeyValueMap::get], but distinguishes missing keys from stale ones
+    /// for maps that can tell the difference.
+    fn lookup(&self, key: &K) -> Result<&V, KeyError> {
+        self.get(key).ok_or(KeyError::Missing)
+    }
+
     fn remove(&mut self, key: &K) -> Option<V>;
 
     fn keys(&'a self) -> Self::Keys;
@@ -26,14 +62,14 @@ where
     K: Ord + 'a,
     V: 'a,
 {
-    type Keys = std::collections::btree_map::Keys<'a, K, V>;
+    type Keys = alloc::collections::btree_map::Keys<'a, K, V>;
 
     fn insert(&mut self, key: K, value: V) -> Option<V> {
         BTreeMap::insert(self, key, value)
     }
 
     fn extend(&mut self, values: impl Iterator<Item = (K, V)>) {
-        std::iter::Extend::extend(self, values)
+        core::iter::Extend::extend(self, values)
     }
 
     fn get(&self, key: &K) -> Option<&V> {
@@ -53,6 +89,7 @@ where
     }
 }
 
+#[cfg(feature = "std")]
 impl<'a, K, V, S> KeyValueMap<'a, K, V> for HashMap<K, V, S>
 where
     K: Hash + Eq + 'a,
@@ -85,4 +122,3 @@ where
         HashMap::keys(self)
     }
 }
-
@@ -1,9 +1,11 @@
-use std::{
+use core::{
     cell::{Ref, RefCell, RefMut},
     ops::{Deref, DerefMut},
-    sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard},
 };
 
+#[cfg(feature = "std")]
+use std::sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
 /// A interior mutable type that can hand out read and write guards to its underlying data.
 pub trait Lock<'a, T>: Default + From<T> {
     type ReadGuard: Deref<Target = T>;
@@ -11,6 +13,37 @@ pub trait Lock<'a, T>: Default + From<T> {
 
     fn read(&'a self) -> Self::ReadGuard;
     fn write(&'a self) -> Self::WriteGuard;
+
+    /// Borrow the data mutably without locking, since `&mut self` rules out any other guard.
+    fn get_mut(&mut self) -> &mut T;
+}
+
+/// A type that can hand out read and write guards to a cell map, as the outer lock of a [Column](crate::Column).
+///
+/// Implemented for every [Lock], and by maps that lock themselves such as [ShardedMap](crate::ShardedMap),
+/// whose guards deref to a view of the map rather than a value it could be built from.
+pub trait MapLock<'a, T> {
+    type ReadGuard: Deref<Target = T>;
+    type WriteGuard: DerefMut<Target = T>;
+
+    fn read(&'a self) -> Self::ReadGuard;
+    fn write(&'a self) -> Self::WriteGuard;
+}
+
+impl<'a, T, L> MapLock<'a, T> for L
+where
+    L: Lock<'a, T>,
+{
+    type ReadGuard = L::ReadGuard;
+    type WriteGuard = L::WriteGuard;
+
+    fn read(&'a self) -> Self::ReadGuard {
+        Lock::read(self)
+    }
+
+    fn write(&'a self) -> Self::WriteGuard {
+        Lock::write(self)
+    }
 }
 
 impl<'a, T> Lock<'a, T> for RefCell<T>
@@ -27,8 +60,13 @@ where
     fn write(&'a self) -> Self::WriteGuard {
         self.borrow_mut()
     }
+
+    fn get_mut(&mut self) -> &mut T {
+        RefCell::get_mut(self)
+    }
 }
 
+#[cfg(feature = "std")]
 impl<'a, T> Lock<'a, T> for Mutex<T>
 where
     T: Default + 'a,
@@ -43,8 +81,13 @@ where
     fn write(&'a self) -> Self::WriteGuard {
         self.lock().expect("poisoned")
     }
+
+    fn get_mut(&mut self) -> &mut T {
+        Mutex::get_mut(self).expect("poisoned")
+    }
 }
 
+#[cfg(feature = "std")]
 impl<'a, T> Lock<'a, T> for RwLock<T>
 where
     T: Default + 'a,
@@ -59,6 +102,10 @@ where
     fn write(&'a self) -> Self::WriteGuard {
         self.write().expect("poisoned")
     }
+
+    fn get_mut(&mut self) -> &mut T {
+        RwLock::get_mut(self).expect("poisoned")
+    }
 }
 
 #[cfg(feature = "parking_lot")]
@@ -76,6 +123,10 @@ where
     fn write(&'a self) -> Self::WriteGuard {
         self.lock()
     }
+
+    fn get_mut(&mut self) -> &mut T {
+        parking_lot::Mutex::get_mut(self)
+    }
 }
 
 #[cfg(feature = "parking_lot")]
@@ -93,4 +144,8 @@ where
     fn write(&'a self) -> Self::WriteGuard {
         self.write()
     }
+
+    fn get_mut(&mut self) -> &mut T {
+        parking_lot::RwLock::get_mut(self)
+    }
 }
@@ -1,8 +1,4 @@
-use std::{
-    cell::{Ref, RefCell, RefMut},
-    ops::{Deref, DerefMut},
-    sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard},
-};
+use core::ops::{Deref, DerefMut};
 
 /// A interior mutable type that can hand out read and write guards to its underlying data.
 #[async_trait::async_trait]
@@ -14,39 +10,36 @@ pub trait LockAsync<'a, T>: Default + From<T> {
     async fn write(&'a self) -> Self::WriteGuard;
 }
 
-#[cfg(feature = "async_std")]
 #[async_trait::async_trait]
 impl<'a, T> LockAsync<'a, T> for async_std::sync::Mutex<T>
 where
-    T: Default + 'a,
+    T: Default + Send + 'a,
 {
     type ReadGuard = async_std::sync::MutexGuard<'a, T>;
     type WriteGuard = async_std::sync::MutexGuard<'a, T>;
 
     async fn read(&'a self) -> Self::ReadGuard {
-        self.lock()
+        self.lock().await
     }
 
     async fn write(&'a self) -> Self::WriteGuard {
-        self.lock()
+        self.lock().await
     }
 }
 
-#[cfg(feature = "async_std")]
 #[async_trait::async_trait]
 impl<'a, T> LockAsync<'a, T> for async_std::sync::RwLock<T>
 where
-    T: Default + 'a,
+    T: Default + Send + Sync + 'a,
 {
     type ReadGuard = async_std::sync::RwLockReadGuard<'a, T>;
     type WriteGuard = async_std::sync::RwLockWriteGuard<'a, T>;
 
     async fn read(&'a self) -> Self::ReadGuard {
-        self.lock()
+        async_std::sync::RwLock::read(self).await
     }
 
     async fn write(&'a self) -> Self::WriteGuard {
-        self.lock()
+        async_std::sync::RwLock::write(self).await
     }
 }
-
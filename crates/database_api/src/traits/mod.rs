@@ -1,13 +1,15 @@
 mod key_set;
 mod key_value_map;
 mod lock;
+mod prefix_range;
 
-#[cfg(feature = "async_trait")]
+#[cfg(feature = "async")]
 mod lock_async;
 
 pub use key_set::*;
 pub use key_value_map::*;
 pub use lock::*;
+pub use prefix_range::*;
 
-#[cfg(feature = "async_trait")]
+#[cfg(feature = "async")]
 pub use lock_async::*;
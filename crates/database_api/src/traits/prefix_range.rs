@@ -0,0 +1,27 @@
+use alloc::collections::BTreeMap;
+
+use crate::table::CompositeKey;
+
+/// A key-value map that can iterate over all entries whose [CompositeKey] starts with a given component.
+pub trait PrefixRange<'a, K, V>
+where
+    K: CompositeKey + 'a,
+    V: 'a,
+{
+    type Range: Iterator<Item = (&'a K, &'a V)>;
+
+    fn prefix_range(&'a self, first: K::First) -> Self::Range;
+}
+
+impl<'a, K, V> PrefixRange<'a, K, V> for BTreeMap<K, V>
+where
+    K: CompositeKey + 'a,
+    V: 'a,
+{
+    type Range = alloc::collections::btree_map::Range<'a, K, V>;
+
+    fn prefix_range(&'a self, first: K::First) -> Self::Range {
+        let (start, end) = K::prefix_bounds(first);
+        self.range(start..=end)
+    }
+}
@@ -1,108 +1,521 @@
-use quote::quote;
+use std::convert::TryInto;
+
+use quote::{format_ident, quote};
 use syn::ItemStruct;
 
-struct ColumnField<'a> {
-    pub ident: syn::Ident,
+pub struct ColumnField<'a> {
+    pub ident: syn::Member,
+    pub marker: syn::Ident,
     pub outer_lock_ty: &'a syn::Type,
-    pub collection_ty: &'a syn::Type,
+    /// The cell map inside the outer lock, or None for maps that are their own outer lock
+    pub collection_ty: Option<&'a syn::Type>,
     pub key_ty: &'a syn::Type,
     pub inner_lock_ty: &'a syn::Type,
     pub inner_ty: &'a syn::Type,
+    /// Values are stored directly in the map, without an inner lock
+    pub unlocked: bool,
 }
 
-pub fn impl_column(input: ItemStruct) -> proc_macro::TokenStream {
+pub fn impl_column(input: ItemStruct) -> syn::Result<proc_macro2::TokenStream> {
     let ident = &input.ident;
-    
-    // Filter the input fields down to valid column types
-    let column_fields = input
-        .fields
-        .iter()
-        .enumerate()
-        .filter_map(|(i, field)| {
-            // Skip any fields explicitly marked with the `skip_column` attribute
-            if field.attrs.iter().any(|attr| {
-                if let Some(last) = attr.path.segments.last() {
-                    last.ident == "skip_column"
-                } else {
-                    false
-                }
-            }) {
-                return None;
-            }
+    let krate = crate_path(&input.attrs)?;
 
-            // If an ident is available, use it. Otherwise this is a tuple type, so use the field index.
-            let ident = field
-                .ident
-                .clone()
-                .unwrap_or_else(|| syn::Ident::new(&i.to_string(), proc_macro2::Span::call_site()));
-
-            // The top-level type is the outer lock
-            let outer_lock_ty = &field.ty;
-
-            // The type inside the outer lock is the collection
-            let collection_ty =
-                if let Some(collection_ty) = get_path_type_generics::<1>(outer_lock_ty, false) {
-                    collection_ty[0]
-                } else {
-                    return None;
-                };
+    let (column_fields, errors) = column_fields(&input);
 
-            // The first and second types in the collection are the key and inner lock
-            // (Certain collections such as HashMap have more generic params, so we allow extra here)
-            let (key_ty, inner_lock_ty) =
-                if let Some(inner_cell_ty) = get_path_type_generics::<2>(collection_ty, true) {
-                    (inner_cell_ty[0], inner_cell_ty[1])
-                } else {
-                    return None;
-                };
+    if let Some(error) = combine_errors(errors) {
+        return Err(error);
+    }
 
-            // The type inside the inner lock is the inner type for this column
-            let inner_ty = if let Some(inner_ty) = get_path_type_generics::<1>(inner_lock_ty, false)
-            {
-                inner_ty[0]
-            } else {
-                return None;
-            };
+    // Each column gets a marker type, in a module named after the table
+    let vis = &input.vis;
+    let marker_module = marker_module_ident(ident);
+    let marker = column_fields.iter().map(|column| &column.marker);
+    let marker_doc = column_fields.iter().map(|column| {
+        let field = &column.ident;
+        format!(
+            "Selects the `{}` column of [{}](super::{}).",
+            quote!(#field),
+            ident,
+            ident
+        )
+    });
+    let module_doc = format!("Column markers for [{}](super::{}).", ident, ident);
 
-            Some(ColumnField {
-                ident,
-                outer_lock_ty,
-                collection_ty,
-                key_ty,
-                inner_lock_ty,
-                inner_ty,
+    // Columns can also be selected by value type, unless another column shares it
+    let is_unambiguous = |column: &ColumnField| {
+        let inner_ty = column.inner_ty;
+        let inner_ty = quote!(#inner_ty).to_string();
+        column_fields
+            .iter()
+            .filter(|other| {
+                let other_ty = other.inner_ty;
+                quote!(#other_ty).to_string() == inner_ty
             })
+            .count()
+            == 1
+    };
+
+    let selector_ty = column_fields
+        .iter()
+        .map(|column| {
+            let marker = &column.marker;
+            quote!(#marker_module::#marker)
         })
+        .chain(
+            column_fields
+                .iter()
+                .filter(|column| is_unambiguous(column))
+                .map(|column| {
+                    let inner_ty = column.inner_ty;
+                    quote!(#inner_ty)
+                }),
+        )
+        .collect::<Vec<_>>();
+
+    let selected_columns = column_fields
+        .iter()
+        .chain(column_fields.iter().filter(|column| is_unambiguous(column)))
         .collect::<Vec<_>>();
 
     // Split ColumnFields iterator into a set of field iterators
-    let field_ident = column_fields.iter().map(|column| &column.ident);
-    let outer_lock_ty = column_fields.iter().map(|column| column.outer_lock_ty);
-    let collection_ty = column_fields.iter().map(|column| column.collection_ty);
-    let key_ty = column_fields.iter().map(|column| column.key_ty);
-    let inner_lock_ty = column_fields.iter().map(|column| column.inner_lock_ty);
-    let inner_ty = column_fields.iter().map(|column| column.inner_ty);
+    let key_ty = selected_columns.iter().map(|column| column.key_ty);
+    let inner_ty = selected_columns.iter().map(|column| column.inner_ty);
+
+    let outer_lock_ty = selected_columns.iter().map(|column| column.outer_lock_ty);
+    let collection_ty = selected_columns
+        .iter()
+        .map(|column| match column.collection_ty {
+            Some(collection_ty) => quote!(#collection_ty),
+            None => locked_map_cells(&krate, column.outer_lock_ty),
+        });
+    // Two-level columns store bare values as their cells, and lend them out directly
+    let inner_lock_ty = selected_columns.iter().map(|column| {
+        let ty = if column.unlocked {
+            column.inner_ty
+        } else {
+            column.inner_lock_ty
+        };
+        quote!(#ty)
+    });
+    let access_ty = selected_columns.iter().map(|column| {
+        if column.unlocked {
+            quote!(#krate::Bare)
+        } else {
+            quote!(#krate::Locked)
+        }
+    });
+    let outer_lock = selected_columns.iter().map(|column| &column.ident);
+
+    // Tables marked #[database(reflect)] can be queried by column name at runtime
+    let reflect = has_database_flag(&input.attrs, "reflect").then(|| {
+        let table_name = ident.to_string();
+        let column_name = column_fields
+            .iter()
+            .map(|column| {
+                let field = &column.ident;
+                quote!(#field).to_string().trim_start_matches("r#").to_string()
+            })
+            .collect::<Vec<_>>();
+        let field_ident = column_fields.iter().map(|column| &column.ident);
+        let guard_ident = (0..column_fields.len())
+            .map(|i| format_ident!("column_{}", i))
+            .collect::<Vec<_>>();
+        let read_value = column_fields.iter().map(|column| {
+            let ColumnField {
+                inner_lock_ty,
+                inner_ty,
+                ..
+            } = column;
+            if column.unlocked {
+                quote!(cell)
+            } else {
+                quote!(&*<#inner_lock_ty as #krate::Lock<'_, #inner_ty>>::read(cell))
+            }
+        });
+
+        quote! {
+            impl #krate::Reflect for #ident {
+                fn table_name(&self) -> &'static str {
+                    #table_name
+                }
+
+                fn column_names(&self) -> &'static [&'static str] {
+                    &[#(#column_name),*]
+                }
+
+                fn read_columns(
+                    &self,
+                    columns: &[&str],
+                ) -> ::core::option::Option<#krate::__private::Vec<#krate::__private::Vec<(#krate::Value, #krate::Value)>>> {
+                    if columns
+                        .iter()
+                        .any(|column| !#krate::Reflect::column_names(self).contains(column))
+                    {
+                        return ::core::option::Option::None;
+                    }
+
+                    // Lock every requested column in declaration order before reading any of them
+                    #(
+                        let #guard_ident = columns
+                            .contains(&#column_name)
+                            .then(|| #krate::MapLock::read(&self.#field_ident));
+                    )*
+
+                    ::core::option::Option::Some(
+                        columns
+                            .iter()
+                            .map(|column| match *column {
+                                #(
+                                    #column_name => {
+                                        let cells = #guard_ident.as_ref().expect("Columns are checked");
+                                        #krate::KeyValueMap::keys(&**cells)
+                                            .filter_map(|key| {
+                                                let cell = #krate::KeyValueMap::get(&**cells, key)?;
+                                                ::core::option::Option::Some((
+                                                    #krate::ReflectValue::to_value(key),
+                                                    #krate::ReflectValue::to_value(#read_value),
+                                                ))
+                                            })
+                                            .collect()
+                                    }
+                                )*
+                                _ => ::core::unreachable!("Columns are checked"),
+                            })
+                            .collect(),
+                    )
+                }
+            }
+        }
+    });
 
     // Generate implementations
     let tokens = quote! {
+        #[doc = #module_doc]
+        #[allow(dead_code)]
+        #vis mod #marker_module {
+            #(
+                #[doc = #marker_doc]
+                #[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+                pub struct #marker;
+            )*
+        }
+
         #(
-            impl<'a> database_api::Column<'a, #key_ty, #inner_ty> for #ident {
+            impl<'a> #krate::Column<'a, #key_ty, #selector_ty> for #ident {
+                type Value = #inner_ty;
                 type OuterLock = #outer_lock_ty;
                 type CellMap = #collection_ty;
                 type InnerLock = #inner_lock_ty;
+                type Access = #access_ty;
 
                 fn outer_lock(&self) -> &Self::OuterLock {
-                    &self.#field_ident
+                    &self.#outer_lock
                 }
             }
         )*
+
+        #reflect
+    };
+
+    Ok(tokens)
+}
+
+/// Filter the input fields down to valid column types, along with errors for invalid ones
+pub fn column_fields(input: &ItemStruct) -> (Vec<ColumnField<'_>>, Vec<syn::Error>) {
+    let mut errors = Vec::<syn::Error>::new();
+
+    let column_fields = input
+        .fields
+        .iter()
+        .enumerate()
+        .filter_map(|(i, field)| {
+            // Skip any fields explicitly marked with the `skip_column` attribute,
+            // along with the primary key, key cache and subscriber fields used by the `Table` derive
+            if ["skip_column", "primary_key", "key_cache", "subscribers"]
+                .iter()
+                .any(|name| has_attribute(field, name))
+            {
+                return None;
+            }
+
+            match column_field(i, field) {
+                Ok(column_field) => Some(column_field),
+                Err(error) => {
+                    errors.push(error);
+                    None
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    (column_fields, errors)
+}
+
+fn column_field(i: usize, field: &syn::Field) -> syn::Result<ColumnField<'_>> {
+    // If an ident is available, use it. Otherwise this is a tuple type, so use the field index.
+    let (ident, marker) = match &field.ident {
+        Some(ident) => (syn::Member::Named(ident.clone()), marker_ident(ident)),
+        None => (syn::Member::Unnamed(i.into()), format_ident!("Column{}", i)),
+    };
+
+    let error = || {
+        syn::Error::new_spanned(
+            &field.ty,
+            "Column fields must have OuterLock<Map<Key, InnerLock<Value>>> structure; \
+             mark other fields with #[skip_column]",
+        )
+    };
+
+    // The top-level type is the outer lock
+    let outer_lock_ty = &field.ty;
+
+    // The type inside the outer lock is the collection, unless the collection locks itself
+    let collection_ty = if is_locked_map(outer_lock_ty) {
+        None
+    } else {
+        Some(get_lock_type_generic(outer_lock_ty).ok_or_else(error)?)
     };
 
-    tokens.into()
+    // The first and second types in the collection are the key and inner lock
+    // (Certain collections such as HashMap have more generic params, so we allow extra here)
+    let [key_ty, inner_lock_ty] =
+        get_path_type_generics::<2>(collection_ty.unwrap_or(outer_lock_ty), true)
+            .ok_or_else(error)?;
+
+    // The type inside the inner lock is the inner type for this column.
+    // Values without one, such as the u32 in RefCell<BTreeMap<usize, u32>>, are stored directly in the map,
+    // except in maps that lock themselves, which have no outer guard to hand out cells through.
+    // Only known locks count, so values like Vec<u32> aren't mistaken for one; #[inner_lock] names others.
+    let inner_locked = has_attribute(field, "inner_lock") || is_known_lock(inner_lock_ty);
+    let (inner_ty, unlocked) = match get_lock_type_generic(inner_lock_ty).filter(|_| inner_locked) {
+        Some(inner_ty) => (inner_ty, false),
+        None if collection_ty.is_some() => (inner_lock_ty, true),
+        None => {
+            return Err(syn::Error::new_spanned(
+                inner_lock_ty,
+                format!(
+                "Values of self-locking maps must be wrapped in an inner lock, i.e. `RwLock<{}>`; \
+                 mark locks the derive doesn't know with #[inner_lock]",
+                quote!(#inner_lock_ty)
+            ),
+            ))
+        }
+    };
+
+    Ok(ColumnField {
+        ident,
+        marker,
+        outer_lock_ty,
+        collection_ty,
+        key_ty,
+        inner_lock_ty,
+        inner_ty,
+        unlocked,
+    })
+}
+
+/// Merge a list of errors into one, so they're all reported at once
+pub fn combine_errors(errors: Vec<syn::Error>) -> Option<syn::Error> {
+    errors.into_iter().reduce(|mut combined, error| {
+        combined.combine(error);
+        combined
+    })
+}
+
+/// The marker type for a column field, i.e. `max_health` -> `MaxHealth`
+pub fn marker_ident(field: &syn::Ident) -> syn::Ident {
+    let camel_case = field
+        .to_string()
+        .trim_start_matches("r#")
+        .split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect::<String>();
+
+    syn::Ident::new(&camel_case, field.span())
+}
+
+/// The module holding a table's column markers, i.e. `EntityTable` -> `entity_table_columns`
+pub fn marker_module_ident(table: &syn::Ident) -> syn::Ident {
+    let mut snake_case = String::new();
+    for (i, c) in table.to_string().chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            snake_case.push('_');
+        }
+        snake_case.extend(c.to_lowercase());
+    }
+
+    format_ident!("{}_columns", snake_case)
+}
+
+/// Lock types that wrap another lock, rather than a value
+const LOCK_WRAPPERS: &[&str] = &["Instrumented"];
+
+/// Lock types the derives recognize as an inner lock around a column's values
+const KNOWN_LOCKS: &[&str] = &["RefCell", "Mutex", "RwLock", "SpinLock", "Atomic", "Mvcc"];
+
+fn is_known_lock(ty: &syn::Type) -> bool {
+    if let syn::Type::Path(syn::TypePath { qself: None, path }) = ty {
+        let last_segment = path.segments.last().expect("No last path segment");
+        KNOWN_LOCKS
+            .iter()
+            .chain(LOCK_WRAPPERS)
+            .any(|lock| last_segment.ident == lock)
+    } else {
+        false
+    }
+}
+
+/// Maps that serve as their own outer lock, along with the cell map their guards deref to
+const LOCKED_MAPS: &[(&str, &str)] = &[("ShardedMap", "ShardedCells")];
+
+fn locked_map_entry(ty: &syn::Type) -> Option<&'static (&'static str, &'static str)> {
+    if let syn::Type::Path(syn::TypePath { qself: None, path }) = ty {
+        let last_segment = path.segments.last().expect("No last path segment");
+        LOCKED_MAPS
+            .iter()
+            .find(|(map, _)| last_segment.ident == map)
+    } else {
+        None
+    }
+}
+
+/// Returns true if the type is a map that serves as its own outer lock, i.e. ShardedMap
+fn is_locked_map(ty: &syn::Type) -> bool {
+    locked_map_entry(ty).is_some()
+}
+
+/// The cell map type of a map that serves as its own outer lock, i.e. ShardedMap<K, V> -> ShardedCells<'a, K, V>
+fn locked_map_cells(krate: &syn::Path, ty: &syn::Type) -> proc_macro2::TokenStream {
+    let (_, cells) = locked_map_entry(ty).expect("Not a locked map");
+    let cells = format_ident!("{}", cells);
+    let args = match ty {
+        syn::Type::Path(syn::TypePath { path, .. }) => {
+            match &path.segments.last().unwrap().arguments {
+                syn::PathArguments::AngleBracketed(arguments) => arguments.args.iter().collect(),
+                _ => Vec::new(),
+            }
+        }
+        _ => Vec::new(),
+    };
+
+    quote!(#krate::#cells<'a, #(#args),*>)
+}
+
+/// Returns true if the field is marked with an attribute of the given name
+pub fn has_attribute(field: &syn::Field, name: &str) -> bool {
+    field.attrs.iter().any(|attr| {
+        if let Some(last) = attr.path.segments.last() {
+            last.ident == name
+        } else {
+            false
+        }
+    })
+}
+
+/// Flags accepted alongside `crate` in the `database` attribute
+const DATABASE_FLAGS: &[&str] = &["reflect"];
+
+/// The path to the database_api crate, overridable with #[database(crate = "path")]
+/// for crates that depend on it under another name or through a re-export.
+///
+/// Every derive reads the `database` attribute through here first, so malformed ones are reported
+/// whichever derives a struct uses.
+pub fn crate_path(attrs: &[syn::Attribute]) -> syn::Result<syn::Path> {
+    let mut krate = None;
+
+    for nested in database_args(attrs)? {
+        match nested {
+            syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                path, lit, ..
+            })) if path.is_ident("crate") => {
+                let lit = match lit {
+                    syn::Lit::Str(lit) => lit,
+                    lit => {
+                        return Err(syn::Error::new_spanned(
+                            lit,
+                            "Expected a string, i.e. #[database(crate = \"path\")]",
+                        ))
+                    }
+                };
+
+                let path = lit.parse::<syn::Path>().map_err(|_| {
+                    syn::Error::new_spanned(&lit, format!("Invalid crate path `{}`", lit.value()))
+                })?;
+                krate = Some(path);
+            }
+            syn::NestedMeta::Meta(syn::Meta::Path(path))
+                if DATABASE_FLAGS.iter().any(|flag| path.is_ident(flag)) => {}
+            nested => {
+                return Err(syn::Error::new_spanned(
+                    nested,
+                    format!(
+                        "Unknown database option; expected `crate = \"path\"` or one of: {}",
+                        DATABASE_FLAGS.join(", ")
+                    ),
+                ))
+            }
+        }
+    }
+
+    Ok(krate.unwrap_or_else(|| syn::parse_quote!(::database_api)))
+}
+
+/// Returns true if the struct is marked with a flag in its `database` attribute, i.e. #[database(reflect)]
+pub fn has_database_flag(attrs: &[syn::Attribute], flag: &str) -> bool {
+    database_args(attrs)
+        .unwrap_or_default()
+        .iter()
+        .any(|nested| matches!(nested, syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident(flag)))
+}
+
+/// The options listed in every `database` attribute
+fn database_args(attrs: &[syn::Attribute]) -> syn::Result<Vec<syn::NestedMeta>> {
+    let mut args = Vec::new();
+
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("database")) {
+        match attr.parse_meta()? {
+            syn::Meta::List(list) => args.extend(list.nested),
+            meta => {
+                return Err(syn::Error::new_spanned(
+                    meta,
+                    "Expected a list of options, i.e. #[database(reflect)]",
+                ))
+            }
+        }
+    }
+
+    Ok(args)
+}
+
+/// Extract the value type from a lock type, seeing through any lock wrappers
+pub fn get_lock_type_generic(input: &syn::Type) -> Option<&syn::Type> {
+    let ty = get_path_type_generics::<1>(input, false)?[0];
+
+    let is_wrapper = if let syn::Type::Path(syn::TypePath { qself: None, path }) = input {
+        let last_segment = path.segments.last().expect("No last path segment");
+        LOCK_WRAPPERS
+            .iter()
+            .any(|wrapper| last_segment.ident == wrapper)
+    } else {
+        false
+    };
+
+    if is_wrapper {
+        get_lock_type_generic(ty)
+    } else {
+        Some(ty)
+    }
 }
 
 /// Extract N generic argument types from a path type
-fn get_path_type_generics<const N: usize>(
+pub fn get_path_type_generics<const N: usize>(
     input: &syn::Type,
     allow_extra: bool,
 ) -> Option<[&syn::Type; N]> {
@@ -138,17 +551,15 @@ fn get_path_type_generics<const N: usize>(
         return None;
     }
 
-    // Zero-allocate out output array
-    let mut child_tys: [&syn::Type; N] = unsafe { std::mem::zeroed() };
-
-    // Write output array
-    for (i, arg) in args.into_iter().take(N).enumerate() {
-        if let syn::GenericArgument::Type(ty) = arg {
-            child_tys[i] = ty;
-        } else {
-            return None;
-        }
-    }
+    // Collect the output array
+    let child_tys = args
+        .into_iter()
+        .take(N)
+        .map(|arg| match arg {
+            syn::GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
 
-    Some(child_tys)
+    child_tys.try_into().ok()
 }
@@ -1,14 +1,27 @@
 mod column;
 mod row;
+mod table;
 
-#[proc_macro_derive(Column, attributes(skip_column))]
+#[proc_macro_derive(Column, attributes(database, inner_lock, skip_column))]
 pub fn derive_column(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = syn::parse_macro_input!(input);
     column::impl_column(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
 }
 
-#[proc_macro_derive(Row, attributes(skip_field))]
+#[proc_macro_derive(Row, attributes(database, column, default, flatten, skip_field))]
 pub fn derive_row(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = syn::parse_macro_input!(input);
     row::impl_row(input)
-}
\ No newline at end of file
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(Table, attributes(database, primary_key, key_cache, subscribers))]
+pub fn derive_table(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input);
+    table::impl_table(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -1,14 +1,37 @@
-use quote::quote;
+use quote::{format_ident, quote};
 use syn::ItemStruct;
 
-struct RowField<'a> {
+use crate::column::{combine_errors, crate_path, has_attribute, marker_ident};
+
+/// How a row field reads its column
+enum Access {
+    /// `&'a T`, holding a read guard
+    Read,
+    /// `&'a mut T`, holding a write guard
+    Write,
+    /// `T`, cloned out from behind a read guard that is released immediately
+    Owned,
+}
+
+/// Where a row field gets its values from
+enum Source {
+    /// A single column, selected by value type or marker
+    Column { column: syn::Path, access: Access },
+    /// Another derived row marked #[flatten], along with the path to its insert struct
+    Flatten { insert: syn::Path },
+}
+
+struct RowField {
     ident: syn::Ident,
-    mutable: bool,
-    ty: &'a syn::TypePath,
+    ty: syn::TypePath,
+    source: Source,
+    /// Marked #[default], so it can be left out of the insert struct's constructor
+    default: bool,
 }
 
-pub fn impl_row(input: ItemStruct) -> proc_macro::TokenStream {
+pub fn impl_row(input: ItemStruct) -> syn::Result<proc_macro2::TokenStream> {
     let ident = &input.ident;
+    let krate = crate_path(&input.attrs)?;
     let generics = &input.generics;
 
     let mut generic_lifetimes = vec![];
@@ -23,7 +46,15 @@ pub fn impl_row(input: ItemStruct) -> proc_macro::TokenStream {
         }
     }
 
-    let _first_generic_lifetime = generic_lifetimes.remove(0);
+    // The first lifetime is the one row fields borrow the table for.
+    // Rows made up only of owned fields don't borrow, so may not have one.
+    let row_lifetime = if generic_lifetimes.is_empty() {
+        None
+    } else {
+        Some(generic_lifetimes.remove(0).lifetime.clone())
+    };
+
+    let mut errors = Vec::<syn::Error>::new();
 
     // Filter the input fields
     let row_fields = input
@@ -31,197 +62,755 @@ pub fn impl_row(input: ItemStruct) -> proc_macro::TokenStream {
         .iter()
         .enumerate()
         .filter_map(|(i, field)| {
-            // Skip any fields explicitly marked with the `skip_column` attribute
-            if field.attrs.iter().any(|attr| {
-                if let Some(last) = attr.path.segments.last() {
-                    last.ident == "skip_field"
-                } else {
-                    false
-                }
-            }) {
+            // Skip any fields explicitly marked with the `skip_field` attribute
+            if has_attribute(field, "skip_field") {
                 return None;
             }
 
-            // If an ident is available, use it. Otherwise this is a tuple type, so use the field index.
-            let ident = field
-                .ident
-                .clone()
-                .unwrap_or_else(|| syn::Ident::new(&i.to_string(), proc_macro2::Span::call_site()));
-
-            // Only reference fields are considered
-            let type_ref = if let syn::Type::Reference(type_ref) = &field.ty {
-                type_ref
-            } else {
-                return None;
-            };
-
-            let mutable = type_ref.mutability.is_some();
+            match row_field(i, field, row_lifetime.as_ref()) {
+                Ok(row_field) => Some(row_field),
+                Err(error) => {
+                    errors.push(error);
+                    None
+                }
+            }
+        })
+        .collect::<Vec<_>>();
 
-            let ty = if let syn::Type::Path(type_path) = type_ref.elem.as_ref() {
-                type_path
-            } else {
-                return None;
-            };
+    if let Some(error) = combine_errors(errors) {
+        return Err(error);
+    }
 
-            Some(RowField { ident, mutable, ty })
+    if row_lifetime.is_none()
+        && row_fields.iter().any(|field| {
+            matches!(
+                field.source,
+                Source::Column {
+                    access: Access::Read | Access::Write,
+                    ..
+                }
+            )
         })
-        .collect::<Vec<_>>();
+    {
+        return Err(syn::Error::new_spanned(
+            ident,
+            "Row structs with borrowed fields need a lifetime, i.e. `struct Row<'a>`",
+        ));
+    }
+
+    let table_lifetime = row_lifetime.as_ref().map(|_| quote!('_table,));
 
     let field_ident = row_fields
         .iter()
-        .map(|column| &column.ident)
+        .map(|field| &field.ident)
         .collect::<Vec<_>>();
 
-    let field_ident_plural = field_ident
-        .iter()
-        .cloned()
-        .map(|ident| syn::Ident::new(&(ident.to_string() + "s"), proc_macro2::Span::call_site()))
-        .collect::<Vec<_>>();
+    // In write methods, a field's ident holds its values and the plural holds its guards
+    let plural = |field: &RowField| format_ident!("{}s", field.ident);
+    let field_ident_plural = row_fields.iter().map(plural).collect::<Vec<_>>();
+
+    let column_of = |column: &syn::Path| quote!(<_Table as #krate::Column<'_table, _Key, #column>>);
+
+    // The values each field takes in the Insert tuple
+    let field_insert_ty = per_field(
+        &row_fields,
+        &krate,
+        |field, _, _| {
+            let ty = &field.ty;
+            quote!(#ty)
+        },
+        |_, _, insert| quote!(<#insert as #krate::RowInsert>::Insert),
+    );
+
+    let field_result_ty = per_field(
+        &row_fields,
+        &krate,
+        |_, column, _| {
+            let column = column_of(column);
+            quote!(::core::option::Option<#column::InnerLock>)
+        },
+        |_, row, _| quote!(#row::Result),
+    );
+
+    let outer_guard_ty = |guard: proc_macro2::TokenStream, row_guards: proc_macro2::TokenStream| {
+        per_field(
+            &row_fields,
+            &krate,
+            |_, column, _| {
+                let column = column_of(column);
+                quote!(<#column::OuterLock as #krate::MapLock<'_table, #column::CellMap>>::#guard)
+            },
+            |_, row, _| quote!(#row::#row_guards),
+        )
+    };
+    let field_outer_read_ty = outer_guard_ty(quote!(ReadGuard), quote!(OuterReadGuards));
+    let field_outer_write_ty = outer_guard_ty(quote!(WriteGuard), quote!(OuterWriteGuards));
+
+    // How a field's column reaches the values in its cell map
+    let cell_access = |field: &RowField, column: &syn::Path| {
+        let ty = &field.ty;
+        let column = column_of(column);
+        quote!(<#column::Access as #krate::CellAccess<'_table, #column::InnerLock, #ty>>)
+    };
 
-    let field_guard = row_fields
-        .iter()
-        .map(|column| {
-            if column.mutable {
-                syn::Ident::new("WriteGuard", proc_macro2::Span::call_site())
-            } else {
-                syn::Ident::new("ReadGuard", proc_macro2::Span::call_site())
+    // The guard held in InnerGuards, or the cloned value itself for owned fields
+    let field_inner_guard = per_field(
+        &row_fields,
+        &krate,
+        |field, column, access| {
+            let ty = &field.ty;
+            let cell_access = cell_access(field, column);
+            match access {
+                Access::Read => quote!(#cell_access::ReadGuard),
+                Access::Write => quote!(#cell_access::WriteGuard),
+                Access::Owned => quote!(#ty),
+            }
+        },
+        |_, row, _| quote!(#row::InnerGuards),
+    );
+
+    // The reference held in InnerRefs, or the cloned value itself for owned fields
+    let field_inner_ref = per_field(
+        &row_fields,
+        &krate,
+        |field, _, access| {
+            let ty = &field.ty;
+            match access {
+                Access::Read => quote!(&'_b #ty),
+                Access::Write => quote!(&'_b mut #ty),
+                Access::Owned => quote!(#ty),
             }
+        },
+        |_, row, _| quote!(#row::InnerRefs<'_b>),
+    );
+
+    let field_read_columns = per_field(
+        &row_fields,
+        &krate,
+        |_, column, _| quote!(#krate::Column::<_Key, #column>::read_cell_map(tbl)),
+        |_, row, _| quote!(#row::read_columns(tbl)),
+    );
+
+    let field_write_columns = per_field(
+        &row_fields,
+        &krate,
+        |_, column, _| quote!(#krate::Column::<_Key, #column>::write_cell_map(tbl)),
+        |_, row, _| quote!(#row::write_columns(tbl)),
+    );
+
+    // Fetch each cell from its cell map, bailing out on missing keys with `unwrap` or `?`
+    let field_cell = |fallible: bool| {
+        per_field(
+            &row_fields,
+            &krate,
+            |field, column, access| {
+                let ident = &field.ident;
+                let cell_map = quote!(::core::ops::Deref::deref(#ident));
+                let cell_access = cell_access(field, column);
+                let cell = if fallible {
+                    quote!(#krate::KeyValueMap::lookup(#cell_map, key)?)
+                } else {
+                    quote!(#krate::KeyValueMap::get(#cell_map, key).unwrap())
+                };
+
+                match access {
+                    Access::Read => quote!(#cell_access::read(#cell)),
+                    Access::Write => quote!(#cell_access::write(#cell)),
+                    Access::Owned => {
+                        quote!(::core::clone::Clone::clone(&*#cell_access::read(#cell)))
+                    }
+                }
+            },
+            |field, row, _| {
+                let ident = &field.ident;
+                if fallible {
+                    quote!(#row::try_get_row(tbl, #ident, key)?)
+                } else {
+                    quote!(#row::get_row(tbl, #ident, key))
+                }
+            },
+        )
+    };
+    let field_get = field_cell(false);
+    let field_try_get = field_cell(true);
+
+    // Borrow each value straight out of its write-locked cell map
+    let field_cell_mut = |fallible: bool| {
+        per_field(
+            &row_fields,
+            &krate,
+            |field, column, access| {
+                let ident = &field.ident;
+                let cell_map = quote!(::core::ops::DerefMut::deref_mut(#ident));
+                let cell_access = cell_access(field, column);
+                let cell = if fallible {
+                    quote!(#krate::KeyValueMap::get_mut(#cell_map, key).ok_or(#krate::KeyError::Missing)?)
+                } else {
+                    quote!(#krate::KeyValueMap::get_mut(#cell_map, key).unwrap())
+                };
+
+                let value = quote!(#cell_access::get_mut(#cell));
+                match access {
+                    Access::Read => quote!(&*#value),
+                    Access::Write => value,
+                    Access::Owned => quote!(::core::clone::Clone::clone(&*#value)),
+                }
+            },
+            |field, row, _| {
+                let ident = &field.ident;
+                if fallible {
+                    quote!(#row::try_get_row_mut(tbl, #ident, key)?)
+                } else {
+                    quote!(#row::get_row_mut(tbl, #ident, key))
+                }
+            },
+        )
+    };
+    let field_get_mut = field_cell_mut(false);
+    let field_try_get_mut = field_cell_mut(true);
+
+    let field_insert = per_field(
+        &row_fields,
+        &krate,
+        |field, _, _| {
+            let (ident, idents) = (&field.ident, plural(field));
+            quote!(#krate::KeyValueMap::insert(::core::ops::DerefMut::deref_mut(#idents), ::core::clone::Clone::clone(&key), ::core::convert::From::from(#ident)))
+        },
+        |field, row, _| {
+            let (ident, idents) = (&field.ident, plural(field));
+            quote!(#row::insert_cells(tbl, #idents, ::core::clone::Clone::clone(&key), #ident))
+        },
+    );
+
+    let field_replace = per_field(
+        &row_fields,
+        &krate,
+        |field, _, _| {
+            let (ident, idents) = (&field.ident, plural(field));
+            quote!(#krate::KeyValueMap::insert(::core::ops::DerefMut::deref_mut(#idents), ::core::clone::Clone::clone(&key), ::core::convert::From::from(#ident)))
+        },
+        |field, row, _| {
+            let (ident, idents) = (&field.ident, plural(field));
+            quote!(#row::replace_cells(tbl, #idents, ::core::clone::Clone::clone(&key), #ident))
+        },
+    );
+
+    let field_extend_push = per_field(
+        &row_fields,
+        &krate,
+        |field, _, _| {
+            let (ident, idents) = (&field.ident, plural(field));
+            quote!(#ident.push((::core::clone::Clone::clone(&key), ::core::convert::Into::into(#idents))))
+        },
+        |field, _, _| {
+            let (ident, idents) = (&field.ident, plural(field));
+            quote!(#ident.push((::core::clone::Clone::clone(&key), #idents)))
+        },
+    );
+
+    let field_extend = per_field(
+        &row_fields,
+        &krate,
+        |field, _, _| {
+            let (ident, idents) = (&field.ident, plural(field));
+            quote!(#krate::KeyValueMap::extend(::core::ops::DerefMut::deref_mut(#idents), ::core::iter::IntoIterator::into_iter(#ident)))
+        },
+        |field, row, _| {
+            let (ident, idents) = (&field.ident, plural(field));
+            quote!(#row::extend_cells(tbl, #idents, ::core::iter::IntoIterator::into_iter(#ident)))
+        },
+    );
+
+    let field_remove = per_field(
+        &row_fields,
+        &krate,
+        |field, _, _| {
+            let idents = plural(field);
+            quote!(#krate::KeyValueMap::remove(::core::ops::DerefMut::deref_mut(#idents), key))
+        },
+        |field, row, _| {
+            let idents = plural(field);
+            quote!(#row::take_cells(tbl, #idents, key))
+        },
+    );
+
+    let field_contains = per_field(
+        &row_fields,
+        &krate,
+        |field, _, _| {
+            let idents = plural(field);
+            quote!(#krate::KeyValueMap::get(::core::ops::Deref::deref(#idents), key).is_some())
+        },
+        |field, row, _| {
+            let idents = plural(field);
+            quote!(#row::contains(#idents, key))
+        },
+    );
+
+    let field_restore = per_field(
+        &row_fields,
+        &krate,
+        |field, _, _| {
+            let (ident, idents) = (&field.ident, plural(field));
+            quote! {
+                match #ident {
+                    ::core::option::Option::Some(cell) => {
+                        #krate::KeyValueMap::insert(::core::ops::DerefMut::deref_mut(#idents), ::core::clone::Clone::clone(&key), cell);
+                        present = true;
+                    }
+                    ::core::option::Option::None => {
+                        #krate::KeyValueMap::remove(::core::ops::DerefMut::deref_mut(#idents), &key);
+                    }
+                }
+            }
+        },
+        |field, row, _| {
+            let (ident, idents) = (&field.ident, plural(field));
+            quote! {
+                #row::restore_cells(tbl, #idents, ::core::clone::Clone::clone(&key), #ident);
+                present |= #row::contains(#idents, &key);
+            }
+        },
+    );
+
+    let field_bound = per_field(
+        &row_fields,
+        &krate,
+        |field, column, access| {
+            let ty = &field.ty;
+            let clone_bound = match access {
+                Access::Owned => Some(quote!(#ty: ::core::clone::Clone,)),
+                _ => None,
+            };
+            quote! {
+                _Table: #krate::Column<'_table, _Key, #column, Value = #ty>,
+                #clone_bound
+            }
+        },
+        |field, _, insert| {
+            let ty = &field.ty;
+            quote!(#ty: #krate::Row<'_table, _Table, _Key, Insert = <#insert as #krate::RowInsert>::Insert>,)
+        },
+    );
+
+    // The insert struct names each value of the Insert tuple, and takes the row's non-lifetime generics
+    let vis = &input.vis;
+    let insert_ident = format_ident!("{}Insert", ident);
+    let insert_params = generic_consts
+        .iter()
+        .map(|ct| {
+            let mut ct = (*ct).clone();
+            ct.eq_token = None;
+            ct.default = None;
+            quote!(#ct)
         })
+        .chain(generic_types.iter().map(|ty| {
+            let mut ty = (*ty).clone();
+            ty.eq_token = None;
+            ty.default = None;
+            quote!(#ty)
+        }))
         .collect::<Vec<_>>();
-
-    let field_borrow_method = row_fields
+    let insert_args = generic_consts
         .iter()
-        .map(|column| {
-            if column.mutable {
-                syn::Ident::new("write_cell", proc_macro2::Span::call_site())
-            } else {
-                syn::Ident::new("read_cell", proc_macro2::Span::call_site())
-            }
-        })
+        .map(|ct| &ct.ident)
+        .chain(generic_types.iter().map(|ty| &ty.ident))
         .collect::<Vec<_>>();
-
-    let field_ty = row_fields
+    let insert_type_args = generic_types.iter().map(|ty| &ty.ident);
+
+    // Flattened rows are named by their own insert struct
+    let insert_field_ty = per_field(
+        &row_fields,
+        &krate,
+        |field, _, _| {
+            let ty = &field.ty;
+            quote!(#ty)
+        },
+        |_, _, insert| quote!(#insert),
+    );
+    let insert_field_value = per_field(
+        &row_fields,
+        &krate,
+        |field, _, _| {
+            let ident = &field.ident;
+            quote!(values.#ident)
+        },
+        |field, _, _| {
+            let ident = &field.ident;
+            quote!(::core::convert::Into::into(values.#ident))
+        },
+    );
+
+    let (default_fields, required_fields): (Vec<_>, Vec<_>) =
+        row_fields.iter().partition(|field| field.default);
+    let default_ident = default_fields.iter().map(|field| &field.ident);
+    let required_ident = required_fields
         .iter()
-        .map(|column| &column.ty)
+        .map(|field| &field.ident)
         .collect::<Vec<_>>();
+    let required_ty = required_fields.iter().map(|field| match &field.source {
+        Source::Column { .. } => {
+            let ty = &field.ty;
+            quote!(#ty)
+        }
+        Source::Flatten { insert } => quote!(#insert),
+    });
+
+    let insert_doc = format!(
+        "Named values for inserting a [`{}`], converting into its `Row::Insert` tuple.",
+        ident
+    );
+    let new_doc = if default_fields.is_empty() {
+        "Construct from every field.".to_string()
+    } else {
+        format!(
+            "Construct from the required fields, leaving {} as their defaults.",
+            default_fields
+                .iter()
+                .map(|field| format!("`{}`", field.ident))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    };
+
+    // Only derive Default when every field may be omitted
+    let insert_default = if required_fields.is_empty() {
+        Some(quote! {
+            impl<#(#insert_params,)*> ::core::default::Default for #insert_ident<#(#insert_args,)*> {
+                fn default() -> Self {
+                    Self::new()
+                }
+            }
+        })
+    } else {
+        None
+    };
 
     // Generate implementations
     let tokens = quote! {
+        #[doc = #insert_doc]
+        #vis struct #insert_ident<#(#insert_params,)*> {
+            #(pub #field_ident: #insert_field_ty,)*
+        }
+
+        impl<#(#insert_params,)*> #insert_ident<#(#insert_args,)*> {
+            #[doc = #new_doc]
+            #[allow(clippy::too_many_arguments)]
+            pub fn new(#(#required_ident: #required_ty),*) -> Self {
+                Self {
+                    #(#required_ident,)*
+                    #(#default_ident: ::core::default::Default::default(),)*
+                }
+            }
+        }
+
+        #insert_default
+
+        impl<#(#insert_params,)*> ::core::convert::From<#insert_ident<#(#insert_args,)*>> for (#(#field_insert_ty,)*) {
+            fn from(values: #insert_ident<#(#insert_args,)*>) -> Self {
+                (#(#insert_field_value,)*)
+            }
+        }
+
+        impl<#(#insert_params,)*> #krate::RowInsert for #insert_ident<#(#insert_args,)*>
+        where
+            #(#insert_type_args: 'static,)*
+        {
+            type Insert = (#(#field_insert_ty,)*);
+        }
+
         #[allow(clippy::type_complexity)]
-        impl<'_table, #(#generic_lifetimes,)* #(#generic_consts,)* _Table, _Key, #(#generic_types,)*> database_api::Row<'_table, _Table, _Key> for #ident<'_table, #(#generic_lifetimes,)* #(#generic_consts,)* #(#generic_types,)*>
+        impl<'_table, #(#generic_lifetimes,)* #(#generic_consts,)* _Table, _Key, #(#generic_types,)*> #krate::Row<'_table, _Table, _Key> for #ident<#table_lifetime #(#generic_lifetimes,)* #(#generic_consts,)* #(#generic_types,)*>
         where
-            _Table: database_api::NextKey<_Key> + database_api::Keys<'_table, _Key> + #(database_api::Column<'_table, _Key, #field_ty>) + *,
-            _Key: Ord + Clone + '_table,
+            _Table: #krate::Keys<'_table, _Key>,
+            _Key: ::core::cmp::Ord + ::core::clone::Clone + '_table,
+            #(#field_bound)*
         {
-            type Insert = (#(#field_ty,)*);
-            type Result = (
-                #(
-                    Option<<_Table as database_api::Column<'_table, _Key, #field_ty>>::InnerLock>,
-                )*
-            );
+            type Insert = (#(#field_insert_ty,)*);
+            type Result = (#(#field_result_ty,)*);
 
-            type OuterReadGuards = (
-                #(
-                    <<_Table as database_api::Column<'_table, _Key, #field_ty>>::OuterLock as database_api::Lock<
-                        '_table,
-                        <_Table as database_api::Column<'_table, _Key, #field_ty>>::CellMap,
-                    >>::ReadGuard,
-                )*
-            );
+            type OuterReadGuards = (#(#field_outer_read_ty,)*);
+            type OuterWriteGuards = (#(#field_outer_write_ty,)*);
 
-            type OuterWriteGuards = (
-                #(
-                    <<_Table as database_api::Column<'_table, _Key, #field_ty>>::OuterLock as database_api::Lock<
-                        '_table,
-                        <_Table as database_api::Column<'_table, _Key, #field_ty>>::CellMap,
-                    >>::WriteGuard,
-                )*
-            );
+            type InnerGuards = (#(#field_inner_guard,)*);
 
-            type InnerGuards = (
-                #(
-                    <<_Table as database_api::Column<'_table, _Key, #field_ty>>::InnerLock as database_api::Lock<'_table, #field_ty>>::#field_guard,
-                )*
-            );
+            type InnerRefs<'_b> = (#(#field_inner_ref,)*)
+            where
+                '_table: '_b,
+                _Table: '_table;
 
             fn read_columns(tbl: &'_table _Table) -> Self::OuterReadGuards {
-                (
-                    #(
-                        database_api::Column::<_Key, #field_ty>::read_cell_map(tbl),
-                    )*
-                )
+                (#(#field_read_columns,)*)
             }
 
+            #[allow(unused_variables)]
             fn get_row(
-                _tbl: &_Table,
+                tbl: &'_table _Table,
                 outer_guards: &'_table Self::OuterReadGuards,
                 key: &_Key,
             ) -> Self::InnerGuards {
                 let (#(#field_ident,)*) = outer_guards;
-                (
-                    #(
-                        database_api::CellMap::#field_borrow_method(#field_ident.deref(), key).unwrap(),
-                    )*
-                )
+                (#(#field_get,)*)
+            }
+
+            fn try_get_row(
+                tbl: &'_table _Table,
+                outer_guards: &'_table Self::OuterReadGuards,
+                key: &_Key,
+            ) -> ::core::result::Result<Self::InnerGuards, #krate::KeyError> {
+                if #krate::Keys::is_stale_key(tbl, key) {
+                    return ::core::result::Result::Err(#krate::KeyError::Stale);
+                }
+
+                let (#(#field_ident,)*) = outer_guards;
+                ::core::result::Result::Ok((#(#field_try_get,)*))
+            }
+
+            fn write_columns(tbl: &'_table _Table) -> Self::OuterWriteGuards {
+                (#(#field_write_columns,)*)
             }
 
-            fn write_columns(tbl: &'_table _Table) -> Self::OuterWriteGuards
+            #[allow(unused_variables)]
+            fn get_row_mut<'_b>(
+                tbl: &'_table _Table,
+                outer_guards: &'_b mut Self::OuterWriteGuards,
+                key: &_Key,
+            ) -> Self::InnerRefs<'_b>
+            where
+                '_table: '_b,
             {
-                (
-                    #(
-                        database_api::Column::<_Key, #field_ty>::write_cell_map(tbl),
-                    )*
-                )
+                let (#(#field_ident,)*) = outer_guards;
+                (#(#field_get_mut,)*)
             }
 
-            fn insert(tbl: &'_table _Table, outer_guards: &mut Self::OuterWriteGuards, key: _Key, values: Self::Insert) -> Self::Result {
-                tbl.insert_key(Self::key_cache_id(tbl), key.clone());
+            fn try_get_row_mut<'_b>(
+                tbl: &'_table _Table,
+                outer_guards: &'_b mut Self::OuterWriteGuards,
+                key: &_Key,
+            ) -> ::core::result::Result<Self::InnerRefs<'_b>, #krate::KeyError>
+            where
+                '_table: '_b,
+            {
+                if #krate::Keys::is_stale_key(tbl, key) {
+                    return ::core::result::Result::Err(#krate::KeyError::Stale);
+                }
+
+                let (#(#field_ident,)*) = outer_guards;
+                ::core::result::Result::Ok((#(#field_try_get_mut,)*))
+            }
+
+            fn insert_cells(tbl: &'_table _Table, outer_guards: &mut Self::OuterWriteGuards, key: _Key, values: impl ::core::convert::Into<Self::Insert>) -> Self::Result {
+                #krate::Keys::insert_key(tbl, Self::key_cache_id(tbl), ::core::clone::Clone::clone(&key));
 
-                let (#(#field_ident,)*) = values;
+                let (#(#field_ident,)*) = ::core::convert::Into::<Self::Insert>::into(values);
                 let (#(#field_ident_plural,)*) = outer_guards;
-                (
-                    #(
-                        database_api::CellMap::insert(#field_ident_plural.deref_mut(), key.clone(), #field_ident),
-                    )*
-                )
+                (#(#field_insert,)*)
             }
 
-            fn extend(tbl: &'_table _Table, outer_guards: &mut Self::OuterWriteGuards, values: impl Iterator<Item = (_Key, Self::Insert)>) {
+            #[allow(unused_variables)]
+            fn replace_cells(tbl: &'_table _Table, outer_guards: &mut Self::OuterWriteGuards, key: _Key, values: impl ::core::convert::Into<Self::Insert>) -> Self::Result {
+                let (#(#field_ident,)*) = ::core::convert::Into::<Self::Insert>::into(values);
+                let (#(#field_ident_plural,)*) = outer_guards;
+                (#(#field_replace,)*)
+            }
+
+            fn extend_cells<_Insert>(tbl: &'_table _Table, outer_guards: &mut Self::OuterWriteGuards, values: impl ::core::iter::Iterator<Item = (_Key, _Insert)>)
+            where
+                _Insert: ::core::convert::Into<Self::Insert>,
+            {
                 let (min, max) = values.size_hint();
                 let length = max.unwrap_or(min);
 
-                let mut keys = Vec::with_capacity(length);
+                let mut keys = #krate::__private::Vec::with_capacity(length);
                 #(
-                    let mut #field_ident = Vec::with_capacity(length);
+                    let mut #field_ident = #krate::__private::Vec::with_capacity(length);
                 )*
 
-                for (key, (#(#field_ident_plural,)*)) in values {
-                    #(
-                        #field_ident.push((key.clone(), #field_ident_plural.into()));
-                    )*
+                for (key, values) in values {
+                    let (#(#field_ident_plural,)*) = ::core::convert::Into::<Self::Insert>::into(values);
+                    #(#field_extend_push;)*
                     keys.push(key);
                 }
 
-                tbl.extend_keys(Self::key_cache_id(tbl), keys.iter().cloned());
+                #krate::Keys::extend_keys(tbl, Self::key_cache_id(tbl), ::core::iter::Iterator::cloned(keys.iter()));
 
                 let (#(#field_ident_plural,)*) = outer_guards;
-                #(
-                    database_api::CellMap::extend(#field_ident_plural.deref_mut(), #field_ident.into_iter());
-                )*
+                #(#field_extend;)*
             }
 
-            fn remove(tbl: &'_table _Table, outer_guards: &mut Self::OuterWriteGuards, key: &_Key) -> Self::Result {
-                tbl.remove_key(&Self::key_cache_id(tbl), key);
+            fn take_cells(tbl: &'_table _Table, outer_guards: &mut Self::OuterWriteGuards, key: &_Key) -> Self::Result {
+                #krate::Keys::remove_key(tbl, &Self::key_cache_id(tbl), key);
+
+                let (#(#field_ident_plural,)*) = outer_guards;
+                (#(#field_remove,)*)
+            }
 
+            fn contains(outer_guards: &Self::OuterWriteGuards, key: &_Key) -> bool {
                 let (#(#field_ident_plural,)*) = outer_guards;
-                (
-                    #(
-                        database_api::CellMap::remove(#field_ident_plural.deref_mut(), key),
-                    )*
+                true #(&& #field_contains)*
+            }
+
+            fn restore_cells(tbl: &'_table _Table, outer_guards: &mut Self::OuterWriteGuards, key: _Key, previous: Self::Result) {
+                let (#(#field_ident,)*) = previous;
+                let (#(#field_ident_plural,)*) = outer_guards;
+                let mut present = false;
+                #(#field_restore)*
+
+                if present {
+                    #krate::Keys::insert_key(tbl, Self::key_cache_id(tbl), key);
+                } else {
+                    #krate::Keys::remove_key(tbl, &Self::key_cache_id(tbl), &key);
+                }
+            }
+        }
+    };
+
+    Ok(tokens)
+}
+
+fn row_field(
+    i: usize,
+    field: &syn::Field,
+    row_lifetime: Option<&syn::Lifetime>,
+) -> syn::Result<RowField> {
+    // If an ident is available, use it. Otherwise this is a tuple type, so name it after the field index.
+    let ident = field
+        .ident
+        .clone()
+        .unwrap_or_else(|| format_ident!("field_{}", i));
+    let default = has_attribute(field, "default");
+
+    if has_attribute(field, "flatten") {
+        return flatten_field(ident, field, row_lifetime, default);
+    }
+
+    // Reference fields borrow their value, other fields clone it
+    let (access, ty) = match &field.ty {
+        syn::Type::Reference(type_ref) => {
+            let access = if type_ref.mutability.is_some() {
+                Access::Write
+            } else {
+                Access::Read
+            };
+            (access, type_ref.elem.as_ref())
+        }
+        ty => (Access::Owned, ty),
+    };
+
+    let ty = if let syn::Type::Path(type_path) = ty {
+        type_path.clone()
+    } else {
+        return Err(syn::Error::new_spanned(
+            ty,
+            "Row fields must be a named column value type, i.e. `T`, `&'a T` or `&'a mut T`; \
+             mark other fields with #[skip_field]",
+        ));
+    };
+
+    // The column is selected by value type, unless named with #[column(field)]
+    let column = match field.attrs.iter().find(|attr| attr.path.is_ident("column")) {
+        Some(attr) => {
+            let mut path = attr.parse_args::<syn::Path>().map_err(|error| {
+                syn::Error::new(
+                    error.span(),
+                    "Expected #[column(field)] or #[column(path::to::Marker)]",
                 )
+            })?;
+            if let Some(field) = path.get_ident() {
+                path = marker_ident(field).into();
             }
+            path
         }
+        None => ty.path.clone(),
     };
 
-    tokens.into()
+    Ok(RowField {
+        ident,
+        ty,
+        source: Source::Column { column, access },
+        default,
+    })
+}
+
+/// Build a token stream per field, from its column or from the flattened row
+fn per_field(
+    row_fields: &[RowField],
+    krate: &syn::Path,
+    column_tokens: impl Fn(&RowField, &syn::Path, &Access) -> proc_macro2::TokenStream,
+    flatten_tokens: impl Fn(&RowField, proc_macro2::TokenStream, &syn::Path) -> proc_macro2::TokenStream,
+) -> Vec<proc_macro2::TokenStream> {
+    row_fields
+        .iter()
+        .map(|field| match &field.source {
+            Source::Column { column, access } => column_tokens(field, column, access),
+            Source::Flatten { insert } => {
+                let ty = &field.ty;
+                flatten_tokens(
+                    field,
+                    quote!(<#ty as #krate::Row<'_table, _Table, _Key>>),
+                    insert,
+                )
+            }
+        })
+        .collect()
+}
+
+/// A field holding another derived row, whose columns are read and written alongside this row's
+fn flatten_field(
+    ident: syn::Ident,
+    field: &syn::Field,
+    row_lifetime: Option<&syn::Lifetime>,
+    default: bool,
+) -> syn::Result<RowField> {
+    let mut ty = match &field.ty {
+        syn::Type::Path(type_path) if type_path.qself.is_none() => type_path.clone(),
+        ty => {
+            return Err(syn::Error::new_spanned(
+                ty,
+                "#[flatten] fields must be a struct deriving Row, i.e. `Inner<'a>`",
+            ))
+        }
+    };
+
+    if let Some(attr) = field.attrs.iter().find(|attr| attr.path.is_ident("column")) {
+        return Err(syn::Error::new_spanned(
+            attr,
+            "#[flatten] fields take their columns from the flattened row, so can't select one",
+        ));
+    }
+
+    // The flattened row borrows the table for as long as this one does
+    let last = ty.path.segments.last_mut().unwrap();
+    if let syn::PathArguments::AngleBracketed(arguments) = &mut last.arguments {
+        for argument in arguments.args.iter_mut() {
+            if let syn::GenericArgument::Lifetime(lifetime) = argument {
+                if Some(&*lifetime) == row_lifetime {
+                    *lifetime = syn::Lifetime::new("'_table", lifetime.span());
+                }
+            }
+        }
+    }
+
+    // The Row derive names insert structs after their row, without its lifetimes
+    let mut insert = ty.path.clone();
+    let last = insert.segments.last_mut().unwrap();
+    last.ident = format_ident!("{}Insert", last.ident);
+    if let syn::PathArguments::AngleBracketed(arguments) = &mut last.arguments {
+        arguments.args = arguments
+            .args
+            .iter()
+            .filter(|argument| !matches!(argument, syn::GenericArgument::Lifetime(_)))
+            .cloned()
+            .collect();
+        if arguments.args.is_empty() {
+            last.arguments = syn::PathArguments::None;
+        }
+    }
+
+    Ok(RowField {
+        ident,
+        ty,
+        source: Source::Flatten { insert },
+        default,
+    })
 }
@@ -0,0 +1,340 @@
+use quote::{format_ident, quote};
+use syn::ItemStruct;
+
+use crate::column::{
+    column_fields, crate_path, get_lock_type_generic, get_path_type_generics, has_attribute,
+};
+
+struct KeyCacheField<'a> {
+    ident: syn::Member,
+    outer_lock_ty: &'a syn::Type,
+    map_ty: &'a syn::Type,
+    inner_lock_ty: &'a syn::Type,
+    set_ty: &'a syn::Type,
+    key_ty: syn::Type,
+}
+
+pub fn impl_table(input: ItemStruct) -> syn::Result<proc_macro2::TokenStream> {
+    let ident = &input.ident;
+    let krate = crate_path(&input.attrs)?;
+
+    // If an ident is available, use it. Otherwise this is a tuple type, so use the field index.
+    let member = |i: usize, field: &syn::Field| {
+        field
+            .ident
+            .clone()
+            .map(syn::Member::Named)
+            .unwrap_or_else(|| syn::Member::Unnamed(i.into()))
+    };
+
+    let mut primary_key_fields = input
+        .fields
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| has_attribute(field, "primary_key"));
+
+    let primary_key = primary_key_fields
+        .next()
+        .map(|(i, field)| (member(i, field), &field.ty));
+
+    // Composite keys can't be generated one component at a time,
+    // as the components would only ever advance together
+    if let Some((_, field)) = primary_key_fields.next() {
+        return Err(syn::Error::new_spanned(
+            field,
+            "Only one field can be marked #[primary_key]; \
+             for composite keys, mark a ManualKeys<(A, B)> field and supply the keys explicitly",
+        ));
+    }
+
+    let mut key_cache_fields = input
+        .fields
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| has_attribute(field, "key_cache"));
+
+    let key_cache = key_cache_fields
+        .next()
+        .map(|(i, field)| -> syn::Result<_> {
+            // The key cache has OuterLock<Map<TypeId, InnerLock<Set<Key>>>> structure
+            let (map_ty, inner_lock_ty, set_ty) = get_lock_type_generic(&field.ty)
+                .and_then(|map_ty| {
+                    let [_, inner_lock_ty] = get_path_type_generics::<2>(map_ty, true)?;
+                    let set_ty = get_lock_type_generic(inner_lock_ty)?;
+                    Some((map_ty, inner_lock_ty, set_ty))
+                })
+                .ok_or_else(|| {
+                    syn::Error::new_spanned(
+                        &field.ty,
+                        "Key cache must have OuterLock<Map<TypeId, InnerLock<Set<Key>>>> structure",
+                    )
+                })?;
+
+            // Sets that aren't generic over their key, such as BitSet,
+            // name it in the attribute instead: #[key_cache(usize)]
+            let key_ty = match get_path_type_generics::<1>(set_ty, true) {
+                Some([key_ty]) => key_ty.clone(),
+                None => field
+                    .attrs
+                    .iter()
+                    .find(|attr| attr.path.is_ident("key_cache"))
+                    .and_then(|attr| attr.parse_args::<syn::Type>().ok())
+                    .ok_or_else(|| {
+                        syn::Error::new_spanned(
+                            set_ty,
+                            "Key cache set has no key type, specify it with #[key_cache(Key)]",
+                        )
+                    })?,
+            };
+
+            Ok(KeyCacheField {
+                ident: member(i, field),
+                outer_lock_ty: &field.ty,
+                map_ty,
+                inner_lock_ty,
+                set_ty,
+                key_ty,
+            })
+        })
+        .transpose()?;
+
+    if let Some((_, field)) = key_cache_fields.next() {
+        return Err(syn::Error::new_spanned(
+            field,
+            "Only one field can be marked #[key_cache]",
+        ));
+    }
+
+    // Subscribers are published to through the Keys impl over the key cache, so require one
+    let subscribers = input
+        .fields
+        .iter()
+        .enumerate()
+        .find(|(_, field)| has_attribute(field, "subscribers"))
+        .map(|(i, field)| -> syn::Result<_> {
+            if key_cache.is_none() {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "#[subscribers] requires a #[key_cache] field to publish from",
+                ));
+            }
+
+            let [key_ty] = get_path_type_generics::<1>(&field.ty, false).ok_or_else(|| {
+                syn::Error::new_spanned(&field.ty, "Subscribers must have Subscribers<Key> type")
+            })?;
+            Ok((member(i, field), key_ty))
+        })
+        .transpose()?;
+
+    let publish = |event: proc_macro2::TokenStream| {
+        subscribers.as_ref().map(|(field_ident, _)| {
+            quote!(#krate::Subscribers::publish(&self.#field_ident, #krate::Event::#event);)
+        })
+    };
+    let publish_remove = publish(quote!(Remove(::core::clone::Clone::clone(key))));
+
+    let publisher = subscribers.as_ref().map(|(field_ident, key_ty)| {
+        quote! {
+            impl #krate::Publisher<#key_ty> for #ident {
+                fn subscribe(&self) -> #krate::Subscription<#key_ty> {
+                    #krate::Subscribers::subscribe(&self.#field_ident)
+                }
+            }
+        }
+    });
+
+    // Delegate NextKey to the primary key field
+    let next_key = primary_key.map(|(field_ident, field_ty)| {
+        quote! {
+            impl<_Key> #krate::NextKey<_Key> for #ident
+            where
+                #field_ty: #krate::NextKey<_Key>,
+            {
+                fn next_key(&self) -> _Key {
+                    #krate::NextKey::next_key(&self.#field_ident)
+                }
+
+                fn free_key(&self, key: &_Key) {
+                    #krate::NextKey::free_key(&self.#field_ident, key)
+                }
+
+                fn is_stale(&self, key: &_Key) -> bool {
+                    #krate::NextKey::is_stale(&self.#field_ident, key)
+                }
+            }
+        }
+    });
+
+    // Implement Keys over the key cache field
+    let keys = key_cache.as_ref().map(|key_cache| {
+        let KeyCacheField {
+            ident: field_ident,
+            outer_lock_ty,
+            map_ty,
+            inner_lock_ty,
+            set_ty,
+            key_ty,
+        } = key_cache;
+
+        // Keys no row type holds any more are freed for recycling, and freed keys are stale
+        let release_key = next_key.as_ref().map(|_| {
+            quote! {
+                fn release_key(&'a self, key: &#key_ty) {
+                    let key_cache = #krate::Lock::read(&self.#field_ident);
+                    let in_use = #krate::KeyValueMap::keys(&*key_cache).any(|type_id| {
+                        #krate::KeyValueMap::get(&*key_cache, type_id)
+                            .is_some_and(|keys| #krate::KeySet::contains(&*#krate::Lock::read(keys), key))
+                    });
+                    if !in_use {
+                        #krate::NextKey::<#key_ty>::free_key(self, key);
+                    }
+                }
+
+                fn is_stale_key(&'a self, key: &#key_ty) -> bool {
+                    #krate::NextKey::<#key_ty>::is_stale(self, key)
+                }
+            }
+        });
+
+        let publish_event = subscribers.as_ref().map(|(field_ident, _)| {
+            quote! {
+                fn publish(&'a self, event: #krate::Event<#key_ty>) {
+                    #krate::Subscribers::publish(&self.#field_ident, event)
+                }
+            }
+        });
+
+        quote! {
+            impl<'a> #krate::Keys<'a, #key_ty> for #ident {
+                type Keys = #krate::CachedKeys<
+                    'a,
+                    #key_ty,
+                    #set_ty,
+                    <#outer_lock_ty as #krate::Lock<'a, #map_ty>>::ReadGuard,
+                    <#inner_lock_ty as #krate::Lock<'a, #set_ty>>::ReadGuard,
+                >;
+
+                fn insert_key(&'a self, type_id: ::core::any::TypeId, key: #key_ty) {
+                    let mut key_cache = #krate::Lock::write(&self.#field_ident);
+                    if #krate::KeyValueMap::get(&*key_cache, &type_id).is_none() {
+                        #krate::KeyValueMap::insert(&mut *key_cache, type_id, ::core::default::Default::default());
+                    }
+
+                    let keys = #krate::KeyValueMap::get(&*key_cache, &type_id).unwrap();
+                    #krate::KeySet::insert(&mut *#krate::Lock::write(keys), key);
+                }
+
+                fn extend_keys(&'a self, type_id: ::core::any::TypeId, keys: impl ::core::iter::Iterator<Item = #key_ty>) {
+                    let mut key_cache = #krate::Lock::write(&self.#field_ident);
+                    if #krate::KeyValueMap::get(&*key_cache, &type_id).is_none() {
+                        #krate::KeyValueMap::insert(&mut *key_cache, type_id, ::core::default::Default::default());
+                    }
+
+                    let cached_keys = #krate::KeyValueMap::get(&*key_cache, &type_id).unwrap();
+                    #krate::KeySet::extend(&mut *#krate::Lock::write(cached_keys), keys);
+                }
+
+                fn remove_key(&'a self, type_id: &::core::any::TypeId, key: &#key_ty) {
+                    let key_cache = #krate::Lock::read(&self.#field_ident);
+                    if let ::core::option::Option::Some(keys) = #krate::KeyValueMap::get(&*key_cache, type_id) {
+                        #krate::KeySet::remove(&mut *#krate::Lock::write(keys), key);
+                    }
+                }
+
+                #release_key
+
+                #publish_event
+
+                fn key_count(&'a self, type_id: &::core::any::TypeId) -> usize {
+                    let key_cache = #krate::Lock::read(&self.#field_ident);
+                    #krate::KeyValueMap::get(&*key_cache, type_id)
+                        .map_or(0, |keys| #krate::KeySet::len(&*#krate::Lock::read(keys)))
+                }
+
+                fn contains_key(&'a self, type_id: &::core::any::TypeId, key: &#key_ty) -> bool {
+                    let key_cache = #krate::Lock::read(&self.#field_ident);
+                    #krate::KeyValueMap::get(&*key_cache, type_id)
+                        .is_some_and(|keys| #krate::KeySet::contains(&*#krate::Lock::read(keys), key))
+                }
+
+                fn keys(&'a self, type_id: &::core::any::TypeId) -> Self::Keys {
+                    #krate::CachedKeys::new(&self.#field_ident, type_id)
+                }
+            }
+        }
+    });
+
+    // Despawn removes a key from every column, keyed like the key cache or the first column.
+    // Invalid columns are reported by the Column derive.
+    let (columns, _) = column_fields(&input);
+    let key_ty = key_cache
+        .as_ref()
+        .map(|key_cache| &key_cache.key_ty)
+        .or_else(|| columns.first().map(|column| column.key_ty));
+
+    let despawn = key_ty.map(|key_ty| {
+        let column_ident = columns.iter().map(|column| &column.ident).collect::<Vec<_>>();
+        let column_guard = (0..columns.len())
+            .map(|i| format_ident!("column_{}", i))
+            .collect::<Vec<_>>();
+        let column_name = column_ident.iter().map(|ident| quote!(#ident).to_string());
+
+        let remove_cached_key = key_cache.as_ref().map(|key_cache| {
+            let field_ident = &key_cache.ident;
+            quote! {
+                let key_cache = #krate::Lock::read(&self.#field_ident);
+                for type_id in #krate::KeyValueMap::keys(&*key_cache) {
+                    if let ::core::option::Option::Some(keys) = #krate::KeyValueMap::get(&*key_cache, type_id) {
+                        #krate::KeySet::remove(&mut *#krate::Lock::write(keys), key);
+                    }
+                }
+            }
+        });
+
+        let free_key = next_key
+            .as_ref()
+            .map(|_| quote!(#krate::NextKey::<#key_ty>::free_key(self, key);));
+
+        quote! {
+            impl #ident {
+                /// Remove `key` from every column and every key cache entry,
+                /// returning the cells that were removed.
+                ///
+                /// Columns are write-locked together, in declaration order.
+                pub fn despawn(&self, key: &#key_ty) -> #krate::Despawned<#key_ty> {
+                    let mut despawned = #krate::Despawned::new(::core::clone::Clone::clone(key));
+
+                    #(
+                        let mut #column_guard = #krate::MapLock::write(&self.#column_ident);
+                    )*
+                    #(
+                        if let ::core::option::Option::Some(cell) = #krate::KeyValueMap::remove(&mut *#column_guard, key) {
+                            despawned.insert(#column_name, #krate::__private::Box::new(cell));
+                        }
+                    )*
+
+                    #remove_cached_key
+                    #publish_remove
+                    #free_key
+
+                    despawned
+                }
+            }
+
+            impl #krate::Despawn<#key_ty> for #ident {
+                fn despawn(&self, key: &#key_ty) -> #krate::Despawned<#key_ty> {
+                    #ident::despawn(self, key)
+                }
+            }
+        }
+    });
+
+    let tokens = quote! {
+        #next_key
+        #keys
+        #publisher
+        #despawn
+    };
+
+    Ok(tokens)
+}
@@ -0,0 +1,5 @@
+// Re-exported under another path, for testing #[database(crate = "...")]
+pub use database_api as db;
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,121 @@
+use std::{
+    any::TypeId,
+    collections::{BTreeMap, BTreeSet},
+    sync::{atomic::AtomicUsize, RwLock},
+};
+
+use database_api::{
+    macros::{Column, Row, Table},
+    FromRow, NextKeyIterator, Row as _,
+};
+
+// Deref and DerefMut are deliberately not imported,
+// so the derives have to get by without them
+
+#[derive(Debug, Default, Column, Table)]
+pub struct Inventory {
+    #[primary_key]
+    primary_key: AtomicUsize,
+
+    #[key_cache]
+    key_cache: RwLock<BTreeMap<TypeId, RwLock<BTreeSet<usize>>>>,
+
+    counts: RwLock<BTreeMap<usize, RwLock<u32>>>,
+    weights: RwLock<BTreeMap<usize, RwLock<f32>>>,
+
+    #[skip_column]
+    name: String,
+}
+
+#[derive(Debug, Row)]
+pub struct CountWeightRow<'a> {
+    count: &'a mut u32,
+    weight: &'a f32,
+
+    #[skip_field]
+    unit: &'static str,
+}
+
+impl<'a, T1, T2> FromRow<'a, (T1, T2)> for CountWeightRow<'a>
+where
+    T1: std::ops::DerefMut<Target = u32>,
+    T2: std::ops::Deref<Target = f32>,
+{
+    fn from_row((count, weight): &'a mut (T1, T2)) -> Self {
+        CountWeightRow {
+            count,
+            weight,
+            unit: "kg",
+        }
+    }
+}
+
+#[derive(Debug, Default, Column, Table)]
+#[database(crate = "crate::db")]
+pub struct Labels {
+    #[primary_key]
+    primary_key: AtomicUsize,
+
+    #[key_cache]
+    key_cache: RwLock<BTreeMap<TypeId, RwLock<BTreeSet<usize>>>>,
+
+    labels: RwLock<BTreeMap<usize, RwLock<String>>>,
+}
+
+#[derive(Debug, Row)]
+#[database(crate = "crate::db")]
+pub struct LabelRow<'a> {
+    label: &'a String,
+}
+
+impl<'a, T> FromRow<'a, (T,)> for LabelRow<'a>
+where
+    T: std::ops::Deref<Target = String>,
+{
+    fn from_row((label,): &'a mut (T,)) -> Self {
+        LabelRow { label }
+    }
+}
+
+#[test]
+fn test_foreign_derives() {
+    let inventory = Inventory::default();
+    assert!(inventory.name.is_empty());
+
+    let mut columns = CountWeightRow::write_columns(&inventory);
+    CountWeightRow::extend(
+        &inventory,
+        &mut columns,
+        NextKeyIterator::new(&inventory).zip((1..=3).map(|i| (i, i as f32 * 0.5))),
+    );
+    drop(columns);
+
+    let columns = CountWeightRow::read_columns(&inventory);
+    for key in CountWeightRow::keys(&inventory) {
+        let mut row = CountWeightRow::get_row(&inventory, &columns, &key);
+        let row = CountWeightRow::from_row(&mut row);
+        *row.count *= 2;
+        assert_eq!(row.unit, "kg");
+        assert_eq!(*row.count as f32, *row.weight * 4.0);
+    }
+}
+
+#[test]
+fn test_crate_path_override() {
+    let labels = Labels::default();
+
+    let mut columns = LabelRow::write_columns(&labels);
+    LabelRow::insert(&labels, &mut columns, 0, ("first".to_string(),));
+    drop(columns);
+
+    let columns = LabelRow::read_columns(&labels);
+    let mut row = LabelRow::try_get_row(&labels, &columns, &0).unwrap();
+    assert_eq!(LabelRow::from_row(&mut row).label, "first");
+}
+
+#[test]
+fn test_derive_diagnostics() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("ui/*.rs");
+    cases.pass("ui/pass/*.rs");
+}
@@ -0,0 +1,11 @@
+use std::sync::RwLock;
+
+use database_api::macros::Column;
+
+#[derive(Column)]
+pub struct Inventory {
+    counts: RwLock<Vec<u32>>,
+    weight: f32,
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use database_api::{macros::Column, ShardedMap};
+
+#[derive(Column)]
+pub struct Inventory {
+    counts: ShardedMap<usize, u32>,
+}
+
+fn main() {}
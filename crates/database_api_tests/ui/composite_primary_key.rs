@@ -0,0 +1,24 @@
+use std::{
+    any::TypeId,
+    collections::{BTreeMap, BTreeSet},
+    sync::{
+        atomic::{AtomicU32, AtomicUsize},
+        RwLock,
+    },
+};
+
+use database_api::macros::Table;
+
+#[derive(Table)]
+pub struct Inventory {
+    #[primary_key]
+    users: AtomicUsize,
+
+    #[primary_key]
+    items: AtomicU32,
+
+    #[key_cache]
+    key_cache: RwLock<BTreeMap<TypeId, RwLock<BTreeSet<(usize, u32)>>>>,
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use database_api::macros::{Column, Row};
+
+#[derive(Column)]
+#[database(reflekt)]
+pub struct Inventory {}
+
+#[derive(Row)]
+#[database(crate = "not a path")]
+pub struct CountRow<'a> {
+    count: &'a u32,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use std::{any::TypeId, collections::BTreeMap, sync::RwLock};
+
+use database_api::{macros::Table, BitSet};
+
+#[derive(Table)]
+pub struct Inventory {
+    #[key_cache]
+    key_cache: RwLock<BTreeMap<TypeId, RwLock<BitSet>>>,
+}
+
+fn main() {}
@@ -0,0 +1,59 @@
+use std::{borrow::Cow, cell::RefCell, collections::BTreeMap, sync::RwLock};
+
+use database_api::{macros::Column, Column, Lock};
+
+// Single-generic values aren't mistaken for inner locks
+#[derive(Debug, Default, Column)]
+pub struct Inventory {
+    items: RefCell<BTreeMap<usize, Vec<u32>>>,
+    owners: RwLock<BTreeMap<usize, Option<usize>>>,
+    labels: RefCell<BTreeMap<usize, Box<str>>>,
+    notes: RefCell<BTreeMap<usize, Cow<'static, str>>>,
+}
+
+// Locks the derive doesn't know are named with #[inner_lock]
+#[derive(Debug, Default)]
+pub struct Custom<T>(RefCell<T>);
+
+impl<'a, T: Default + 'a> Lock<'a, T> for Custom<T> {
+    type ReadGuard = std::cell::Ref<'a, T>;
+    type WriteGuard = std::cell::RefMut<'a, T>;
+
+    fn read(&'a self) -> Self::ReadGuard {
+        self.0.borrow()
+    }
+
+    fn write(&'a self) -> Self::WriteGuard {
+        self.0.borrow_mut()
+    }
+
+    fn get_mut(&mut self) -> &mut T {
+        self.0.get_mut()
+    }
+}
+
+impl<T> From<T> for Custom<T> {
+    fn from(value: T) -> Self {
+        Custom(RefCell::new(value))
+    }
+}
+
+#[derive(Debug, Default, Column)]
+pub struct Counters {
+    #[inner_lock]
+    counts: RefCell<BTreeMap<usize, Custom<u32>>>,
+}
+
+fn assert_column<'a, T, V>()
+where
+    T: Column<'a, usize, V, Value = V>,
+{
+}
+
+fn main() {
+    assert_column::<Inventory, Vec<u32>>();
+    assert_column::<Inventory, Option<usize>>();
+    assert_column::<Inventory, Box<str>>();
+    assert_column::<Inventory, Cow<'static, str>>();
+    assert_column::<Counters, u32>();
+}
@@ -0,0 +1,12 @@
+use database_api::macros::Row;
+
+#[derive(Row)]
+pub struct CountRow<'a> {
+    count: &'a mut u32,
+    weights: [f32; 2],
+    label: &'a [u8],
+    #[flatten]
+    inner: &'a CountRow<'a>,
+}
+
+fn main() {}
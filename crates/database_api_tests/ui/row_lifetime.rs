@@ -0,0 +1,8 @@
+use database_api::macros::Row;
+
+#[derive(Row)]
+pub struct CountRow {
+    count: &'static u32,
+}
+
+fn main() {}
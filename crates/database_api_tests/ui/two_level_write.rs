@@ -0,0 +1,45 @@
+use std::{
+    any::TypeId,
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    ops::DerefMut,
+    sync::atomic::AtomicUsize,
+};
+
+use database_api::{
+    macros::{Column, Row, Table},
+    FromRow, Row as _,
+};
+
+#[derive(Debug, Default, Column, Table)]
+pub struct Tally {
+    #[primary_key]
+    primary_key: AtomicUsize,
+
+    #[key_cache]
+    key_cache: RefCell<BTreeMap<TypeId, RefCell<BTreeSet<usize>>>>,
+
+    counts: RefCell<BTreeMap<usize, u32>>,
+}
+
+#[derive(Debug, Row)]
+pub struct CountRow<'a> {
+    count: &'a mut u32,
+}
+
+impl<'a, T> FromRow<'a, (T,)> for CountRow<'a>
+where
+    T: DerefMut<Target = u32>,
+{
+    fn from_row((count,): &'a mut (T,)) -> Self {
+        CountRow { count }
+    }
+}
+
+// Bare values can't be written through read guards; get_row_mut borrows them from write guards
+fn main() {
+    let table = Tally::default();
+    let columns = CountRow::read_columns(&table);
+    let mut row = CountRow::get_row(&table, &columns, &0);
+    *CountRow::from_row(&mut row).count += 1;
+}